@@ -43,7 +43,12 @@
       in {
         packages.breakout-bin = craneLib.buildPackage {
           name = "breakout-bin";
-          src = craneLib.cleanCargoSource ./.;
+          # The gameplay tests play the levels from the assets
+          src = pkgs.lib.cleanSourceWith {
+            src = ./.;
+            filter = path: type:
+              (craneLib.filterCargoSources path type) || (pkgs.lib.hasInfix "/assets" path);
+          };
           inherit buildInputs;
           inherit nativeBuildInputs;
         };
@@ -123,6 +128,7 @@
         defaultApp = self.apps.${system}.breakout;
 
         checks = {
+          inherit (self.packages.${system}) breakout-bin;
           pre-commit-check = inputs.pre-commit-hooks.lib.${system}.run {
             src = ./.;
             hooks = {
@@ -0,0 +1,269 @@
+use std::collections::{BTreeMap, HashSet};
+
+use bevy::{input::InputSystem, prelude::*};
+use serde::{Deserialize, Serialize};
+
+/// File the bindings are kept in between runs, next to the game
+#[cfg(not(target_arch = "wasm32"))]
+const BINDINGS_PATH: &str = "bindings.ron";
+
+/// Something the local player can do, whatever it is bound to
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum Action {
+    MoveLeft,
+    MoveRight,
+    Serve,
+    Pause,
+    Restart,
+    /// Moves the paddle of the second player when two play at the same time
+    SecondMoveLeft,
+    SecondMoveRight,
+    SecondServe,
+}
+
+impl Action {
+    pub(crate) const ALL: [Action; 8] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Serve,
+        Action::Pause,
+        Action::Restart,
+        Action::SecondMoveLeft,
+        Action::SecondMoveRight,
+        Action::SecondServe,
+    ];
+
+    /// The actions of the second player, who doesn't take part in switching input devices
+    pub(crate) const SECOND_PLAYER: [Action; 3] = [
+        Action::SecondMoveLeft,
+        Action::SecondMoveRight,
+        Action::SecondServe,
+    ];
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            Action::MoveLeft => "move left",
+            Action::MoveRight => "move right",
+            Action::Serve => "serve",
+            Action::Pause => "pause",
+            Action::Restart => "restart",
+            Action::SecondMoveLeft => "p2 move left",
+            Action::SecondMoveRight => "p2 move right",
+            Action::SecondServe => "p2 serve",
+        }
+    }
+}
+
+/// A button an action can be bound to
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// The button on any connected gamepad
+    Gamepad(GamepadButtonType),
+}
+
+impl Binding {
+    fn same_device(&self, other: &Binding) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    pub(crate) fn label(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{key:?}"),
+            Binding::Mouse(button) => format!("mouse {button:?}"),
+            Binding::Gamepad(button) => format!("pad {button:?}"),
+        }
+    }
+}
+
+/// The buttons every action is bound to
+#[derive(Resource, Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct Bindings(pub(crate) BTreeMap<Action, Vec<Binding>>);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use Binding::{Gamepad, Key, Mouse};
+
+        Self(BTreeMap::from([
+            (
+                Action::MoveLeft,
+                vec![
+                    Key(KeyCode::Left),
+                    Key(KeyCode::A),
+                    Gamepad(GamepadButtonType::DPadLeft),
+                ],
+            ),
+            (
+                Action::MoveRight,
+                vec![
+                    Key(KeyCode::Right),
+                    Key(KeyCode::D),
+                    Gamepad(GamepadButtonType::DPadRight),
+                ],
+            ),
+            (
+                Action::Serve,
+                vec![
+                    Key(KeyCode::Space),
+                    Mouse(MouseButton::Left),
+                    Gamepad(GamepadButtonType::South),
+                ],
+            ),
+            (
+                Action::Pause,
+                vec![
+                    Key(KeyCode::Escape),
+                    Key(KeyCode::P),
+                    Gamepad(GamepadButtonType::Start),
+                ],
+            ),
+            (
+                Action::Restart,
+                vec![
+                    Key(KeyCode::Space),
+                    Key(KeyCode::R),
+                    Mouse(MouseButton::Left),
+                    Gamepad(GamepadButtonType::South),
+                ],
+            ),
+            (Action::SecondMoveLeft, vec![Key(KeyCode::J)]),
+            (Action::SecondMoveRight, vec![Key(KeyCode::L)]),
+            (Action::SecondServe, vec![Key(KeyCode::K)]),
+        ]))
+    }
+}
+
+impl Bindings {
+    pub(crate) fn get(&self, action: Action) -> &[Binding] {
+        self.0.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether the button is bound to any of these actions
+    pub(crate) fn is_bound(&self, actions: &[Action], binding: Binding) -> bool {
+        actions
+            .iter()
+            .any(|action| self.get(*action).contains(&binding))
+    }
+
+    /// Bind an action to a button, in place of its buttons on the same device
+    pub(crate) fn rebind(&mut self, action: Action, binding: Binding) {
+        let bindings = self.0.entry(action).or_default();
+        bindings.retain(|bound| !bound.same_device(&binding));
+        bindings.push(binding);
+    }
+
+    /// Read the saved bindings over the defaults, so actions added since they were saved keep
+    /// their default buttons
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn load() -> Self {
+        let mut bindings = Self::default();
+        let Ok(saved) = std::fs::read_to_string(BINDINGS_PATH) else {
+            return bindings;
+        };
+        match ron::from_str::<Bindings>(&saved) {
+            Ok(saved) => bindings.0.extend(saved.0),
+            Err(error) => warn!("Ignoring invalid bindings in {BINDINGS_PATH}: {error}"),
+        }
+        bindings
+    }
+
+    /// Browsers have no file to keep the bindings in
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn load() -> Self {
+        default()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn save(&self) {
+        let result = ron::ser::to_string_pretty(self, default())
+            .map_err(|error| error.to_string())
+            .and_then(|bindings| {
+                std::fs::write(BINDINGS_PATH, bindings).map_err(|error| error.to_string())
+            });
+        if let Err(error) = result {
+            warn!("Could not save the bindings to {BINDINGS_PATH}: {error}");
+        }
+    }
+
+    /// Bindings only last for the session in browsers
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn save(&self) {}
+}
+
+/// Which actions are held down this frame
+#[derive(Resource, Default)]
+pub(crate) struct ActionState {
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+}
+
+impl ActionState {
+    pub(crate) fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub(crate) fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+}
+
+pub(crate) fn action_just_pressed(action: Action) -> impl Fn(Res<ActionState>) -> bool {
+    move |action_state: Res<ActionState>| action_state.just_pressed(action)
+}
+
+fn update_action_state(
+    bindings: Res<Bindings>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_button_input: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_button_input: Res<Input<GamepadButton>>,
+    mut action_state: ResMut<ActionState>,
+) {
+    let gamepad_buttons = |button_type| {
+        gamepads
+            .iter()
+            .map(move |gamepad| GamepadButton::new(gamepad, button_type))
+    };
+    let pressed = |binding: &Binding| match *binding {
+        Binding::Key(key) => keyboard_input.pressed(key),
+        Binding::Mouse(button) => mouse_button_input.pressed(button),
+        Binding::Gamepad(button_type) => {
+            gamepad_button_input.any_pressed(gamepad_buttons(button_type))
+        }
+    };
+    let just_pressed = |binding: &Binding| match *binding {
+        Binding::Key(key) => keyboard_input.just_pressed(key),
+        Binding::Mouse(button) => mouse_button_input.just_pressed(button),
+        Binding::Gamepad(button_type) => {
+            gamepad_button_input.any_just_pressed(gamepad_buttons(button_type))
+        }
+    };
+
+    let action_state = action_state.as_mut();
+    action_state.pressed.clear();
+    action_state.just_pressed.clear();
+    for action in Action::ALL {
+        let bound = bindings.get(action);
+        if bound.iter().any(pressed) {
+            action_state.pressed.insert(action);
+        }
+        if bound.iter().any(just_pressed) {
+            action_state.just_pressed.insert(action);
+        }
+    }
+}
+
+pub(crate) struct ActionsPlugin;
+
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Bindings::load())
+            .init_resource::<ActionState>()
+            .add_system(
+                update_action_state
+                    .in_base_set(CoreSet::PreUpdate)
+                    .after(InputSystem),
+            );
+    }
+}
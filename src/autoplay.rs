@@ -0,0 +1,544 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    bounce::PaddleBounce,
+    breakout::{
+        snapshot_inputs, BallSpeedScale, BreakoutConfig, BreakoutState, Court, Paddle, PaddleInput,
+        PaddleInputs, SimulationSet,
+    },
+    collision::Collider,
+    headless::{ball_states, brick_states, paddle_states, BallState, BrickState, PaddleState},
+    players::GameMode,
+};
+
+/// Seconds the result of a game played by a bot stays up before the next game starts
+const DEMO_RESTART_DELAY: f32 = 5.;
+
+/// How far off center the perfect tracker hits the ball, in turn, normalized to `[-1, 1]`
+/// across the paddle
+const PERFECT_TRACKER_OFFSETS: [f32; 6] = [0.3, -0.5, 0.15, -0.25, 0.5, -0.1];
+
+/// Furthest off center the aiming bot hits the ball, normalized to `[-1, 1]` across the paddle
+const MAX_AIM_OFFSET: f32 = 0.8;
+
+/// Number of hit positions across the paddle the aiming bot considers
+const AIM_STEPS: usize = 33;
+
+/// What a bot sees of the court its paddle is in, in the coordinates of the court
+pub struct BotView {
+    /// The paddle the bot drives
+    pub paddle: PaddleState,
+    /// Lowest and highest the paddle can go
+    pub paddle_range: (f32, f32),
+    /// Balls in the court, including those held on a paddle
+    pub balls: Vec<BallState>,
+    /// Bricks standing in the court
+    pub bricks: Vec<BrickState>,
+    pub court_size: Vec2,
+    /// Width and height of a ball
+    pub ball_size: f32,
+    /// Top speed of the paddle in world units per second
+    pub paddle_speed: f32,
+    /// How much faster than their velocity the balls in the court travel
+    pub ball_speed_scale: f32,
+    /// Length of a tick in seconds
+    pub tick_duration: f32,
+    paddle_bounce: PaddleBounce,
+    angle_multiplier: f32,
+}
+
+/// Where the paddle gets to hit a ball next
+#[derive(Clone, Copy, Debug)]
+pub struct Catch {
+    /// Position of the center of the ball when it comes down onto the paddle
+    pub x: f32,
+    /// Seconds until then
+    pub seconds: f32,
+    /// Speed of the ball, which it leaves the paddle with
+    pub speed: f32,
+}
+
+impl BotView {
+    /// Move direction that takes the paddle to `x` as fast as it goes, without overshooting it
+    pub fn move_towards(&self, x: f32) -> f32 {
+        let max_step = self.paddle_speed * self.tick_duration;
+        ((x - self.paddle.position.x) / max_step).clamp(-1., 1.)
+    }
+
+    /// Height of the center of a ball touching the top of the paddle
+    pub fn contact_height(&self) -> f32 {
+        self.paddle.position.y + (self.paddle.size.y + self.ball_size) / 2.
+    }
+
+    /// Where the paddle has to be to hit a ball at `x` at `distance_from_center` from its center,
+    /// normalized to `[-1, 1]` across the paddle
+    pub fn paddle_x_for(&self, x: f32, distance_from_center: f32) -> f32 {
+        x - distance_from_center * (self.paddle.size.x + self.ball_size) / 2.
+    }
+
+    /// Where a ball is when it comes down to `height` and in how many seconds, bouncing off the
+    /// walls and the top of the court on the way, but going straight through the bricks
+    pub fn predict(&self, ball: &BallState, height: f32) -> Option<(f32, f32)> {
+        let velocity = ball.velocity * self.ball_speed_scale;
+        if ball.held || velocity.y == 0. {
+            return None;
+        }
+        let limit = self.court_size / 2. - self.ball_size / 2.;
+        let distance = if velocity.y < 0. {
+            ball.position.y - height
+        } else {
+            (limit.y - ball.position.y) + (limit.y - height)
+        };
+        if distance < 0. {
+            return None;
+        }
+        let seconds = distance / velocity.y.abs();
+        Some((
+            fold_into(ball.position.x + velocity.x * seconds, limit.x),
+            seconds,
+        ))
+    }
+
+    /// The ball that comes down onto the paddle first
+    pub fn next_catch(&self) -> Option<Catch> {
+        let height = self.contact_height();
+        self.balls
+            .iter()
+            .filter_map(|ball| {
+                let (x, seconds) = self.predict(ball, height)?;
+                Some(Catch {
+                    x,
+                    seconds,
+                    speed: ball.velocity.length(),
+                })
+            })
+            .min_by(|a, b| a.seconds.total_cmp(&b.seconds))
+    }
+
+    /// A ball held on the paddle, waiting to be served
+    pub fn held_ball(&self) -> Option<&BallState> {
+        let reach = (self.paddle.size.x + self.ball_size) / 2.;
+        self.balls
+            .iter()
+            .find(|ball| ball.held && (ball.position.x - self.paddle.position.x).abs() <= reach)
+    }
+
+    /// Velocity of a ball of the given speed leaving the standing paddle at `distance_from_center`
+    pub fn exit_velocity(&self, speed: f32, distance_from_center: f32) -> Vec2 {
+        self.paddle_bounce
+            .exit_velocity(speed, distance_from_center, 0., self.angle_multiplier)
+    }
+
+    /// Whether the paddle can be at `x` in time
+    fn reachable(&self, x: f32, seconds: f32) -> bool {
+        x >= self.paddle_range.0
+            && x <= self.paddle_range.1
+            && (x - self.paddle.position.x).abs() <= self.paddle_speed * seconds
+    }
+}
+
+/// Bounce a position back and forth between `-limit` and `limit`, like a ball between two walls
+fn fold_into(x: f32, limit: f32) -> f32 {
+    if limit <= 0. {
+        return 0.;
+    }
+    let folded = (x + limit).rem_euclid(4. * limit);
+    if folded <= 2. * limit {
+        folded - limit
+    } else {
+        3. * limit - folded
+    }
+}
+
+/// A way of playing a paddle, picking its input every tick from what it sees of the court
+///
+/// Serve only has to be held for the bot to serve, it is let go of in between serves for it.
+pub trait Strategy: Send + Sync {
+    fn input(&mut self, view: &BotView) -> PaddleInput;
+}
+
+/// Never misses, meeting every ball where it comes down
+///
+/// Each ball is hit a different distance off center than the one before, so it doesn't bounce
+/// straight up and down forever.
+#[derive(Default)]
+pub struct PerfectTracker {
+    /// Where the ball was going to come down, and the offset picked for it
+    aim: Option<(f32, f32)>,
+    hits: usize,
+}
+
+impl Strategy for PerfectTracker {
+    fn input(&mut self, view: &BotView) -> PaddleInput {
+        let Some(catch) = view.next_catch() else {
+            return PaddleInput {
+                serve: view.held_ball().is_some(),
+                ..default()
+            };
+        };
+
+        let offset = match self.aim {
+            Some((x, offset)) if (x - catch.x).abs() < view.ball_size => offset,
+            _ => {
+                self.hits += 1;
+                PERFECT_TRACKER_OFFSETS[self.hits % PERFECT_TRACKER_OFFSETS.len()]
+            }
+        };
+        self.aim = Some((catch.x, offset));
+
+        PaddleInput {
+            move_direction: view.move_towards(view.paddle_x_for(catch.x, offset)),
+            serve: false,
+        }
+    }
+}
+
+/// Plays like a person: reacts late, misjudges where the ball comes down and takes a moment
+/// before serving
+pub struct HumanTracker {
+    /// Seconds it takes to act on what the ball does
+    pub reaction_time: f32,
+    /// How far off the player aims on average, as a fraction of the width of the paddle
+    pub aim_error: f32,
+    rng: ChaCha8Rng,
+    /// Where the player decided to go on each tick of the reaction time, oldest first
+    plans: VecDeque<Option<f32>>,
+    /// Where the ball was going to come down, and how far off the player judged it
+    aim: Option<(f32, f32)>,
+    /// Seconds left before serving the held ball
+    serve_delay: Option<f32>,
+}
+
+impl HumanTracker {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            reaction_time: 0.2,
+            aim_error: 0.25,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            plans: VecDeque::new(),
+            aim: None,
+            serve_delay: None,
+        }
+    }
+
+    fn serve(&mut self, view: &BotView) -> bool {
+        if view.held_ball().is_none() {
+            self.serve_delay = None;
+            return false;
+        }
+        let delay = self
+            .serve_delay
+            .get_or_insert_with(|| self.rng.gen_range(0.3..1.));
+        *delay -= view.tick_duration;
+        *delay <= 0.
+    }
+}
+
+impl Strategy for HumanTracker {
+    fn input(&mut self, view: &BotView) -> PaddleInput {
+        let plan = view.next_catch().map(|catch| {
+            let error = match self.aim {
+                Some((x, error)) if (x - catch.x).abs() < view.ball_size => error,
+                _ => {
+                    // Roughly normally distributed, with a standard deviation of `aim_error`
+                    let spread: f32 = (0..3).map(|_| self.rng.gen_range(-1.0..1.)).sum();
+                    spread * self.aim_error * view.paddle.size.x
+                }
+            };
+            self.aim = Some((catch.x, error));
+            catch.x + error
+        });
+
+        self.plans.push_back(plan);
+        let reaction_ticks = (self.reaction_time / view.tick_duration).round() as usize;
+        let mut acted_on = None;
+        while self.plans.len() > reaction_ticks {
+            acted_on = self.plans.pop_front();
+        }
+
+        PaddleInput {
+            move_direction: acted_on
+                .flatten()
+                .map_or(0., |target| view.move_towards(target)),
+            serve: self.serve(view),
+        }
+    }
+}
+
+/// Predicts where every ball comes down, and hits it off the part of the paddle that sends it
+/// towards the lowest of the remaining bricks
+#[derive(Default)]
+pub struct AimingBot;
+
+impl AimingBot {
+    /// The lowest breakable brick, the one nearest to `x` of those as low
+    fn target(view: &BotView, x: f32) -> Option<&BrickState> {
+        let bottom = |brick: &BrickState| brick.position.y - brick.size.y / 2.;
+        let breakable = view.bricks.iter().filter(|brick| brick.breakable);
+        let lowest = breakable.clone().map(bottom).reduce(f32::min)?;
+        breakable
+            .filter(|brick| bottom(brick) < lowest + view.ball_size)
+            .min_by(|a, b| {
+                (a.position.x - x)
+                    .abs()
+                    .total_cmp(&(b.position.x - x).abs())
+            })
+    }
+
+    /// Where the paddle has to be to send the ball from `catch` to `target`, if it gets there
+    /// in time
+    fn aim(view: &BotView, catch: &Catch, target: &BrickState) -> Option<f32> {
+        let limit = view.court_size.x / 2. - view.ball_size / 2.;
+        let target_height = target.position.y - (target.size.y + view.ball_size) / 2.;
+        let rise = target_height - view.contact_height();
+
+        (0..AIM_STEPS)
+            .map(|step| MAX_AIM_OFFSET * (2. * step as f32 / (AIM_STEPS - 1) as f32 - 1.))
+            .filter_map(|offset| {
+                let paddle_x = view.paddle_x_for(catch.x, offset);
+                if !view.reachable(paddle_x, catch.seconds) {
+                    return None;
+                }
+                let velocity = view.exit_velocity(catch.speed, offset);
+                if velocity.y <= 0. {
+                    return None;
+                }
+                let x = fold_into(catch.x + velocity.x * rise / velocity.y, limit);
+                Some(((x - target.position.x).abs(), offset.abs(), paddle_x))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)))
+            .map(|(.., paddle_x)| paddle_x)
+    }
+}
+
+impl Strategy for AimingBot {
+    fn input(&mut self, view: &BotView) -> PaddleInput {
+        // Serves go straight up, so line up under the target first
+        if view.held_ball().is_some() {
+            let Some(target) = Self::target(view, view.paddle.position.x) else {
+                return PaddleInput {
+                    serve: true,
+                    ..default()
+                };
+            };
+            let x = target
+                .position
+                .x
+                .clamp(view.paddle_range.0, view.paddle_range.1);
+            return PaddleInput {
+                move_direction: view.move_towards(x),
+                serve: (x - view.paddle.position.x).abs() < 1.,
+            };
+        }
+
+        let Some(catch) = view.next_catch() else {
+            return PaddleInput::default();
+        };
+        let paddle_x = Self::target(view, catch.x)
+            .and_then(|target| Self::aim(view, &catch, target))
+            .unwrap_or(catch.x);
+        PaddleInput {
+            move_direction: view.move_towards(paddle_x),
+            serve: false,
+        }
+    }
+}
+
+/// The built in strategies, by the name they go by on the command line
+pub(crate) fn strategy_by_name(name: &str, seed: u64) -> Result<Box<dyn Strategy>, String> {
+    match name {
+        "perfect" => Ok(Box::<PerfectTracker>::default()),
+        "human" => Ok(Box::new(HumanTracker::new(seed))),
+        "aiming" => Ok(Box::<AimingBot>::default()),
+        _ => Err(format!(
+            "unknown strategy {name}, expected perfect, human or aiming"
+        )),
+    }
+}
+
+/// A bot driving one of the paddle inputs
+struct Bot {
+    input: usize,
+    strategy: Box<dyn Strategy>,
+    /// Whether the bot held serve on the last tick
+    serving: bool,
+}
+
+/// The bots playing in place of players, which write `PaddleInputs` like any other input backend
+#[derive(Resource, Default)]
+pub(crate) struct Bots {
+    bots: Vec<Bot>,
+    /// Game mode the bot on the first input picks and keeps playing, as a demo
+    pub(crate) demo: Option<GameMode>,
+}
+
+impl Bots {
+    /// Have a bot drive the paddles of an input, in place of whoever drove them before
+    pub(crate) fn add(&mut self, input: usize, strategy: Box<dyn Strategy>) {
+        self.bots.retain(|bot| bot.input != input);
+        self.bots.push(Bot {
+            input,
+            strategy,
+            serving: false,
+        });
+    }
+
+    fn drives(&self, input: usize) -> bool {
+        self.bots.iter().any(|bot| bot.input == input)
+    }
+}
+
+fn bots_playing(bots: Res<Bots>) -> bool {
+    !bots.bots.is_empty()
+}
+
+fn demo_playing(bots: Res<Bots>) -> bool {
+    bots.demo.is_some() && bots.drives(0)
+}
+
+/// Let every bot pick the input of its paddle for this tick
+fn bot_inputs(world: &mut World) {
+    let state = world.resource::<State<BreakoutState>>().0.clone();
+    let paddles = paddle_states(world);
+    let balls = ball_states(world);
+    let bricks = brick_states(world);
+    let court_speed_scales: Vec<(usize, f32)> = world
+        .iter_entities()
+        .filter_map(|entity| entity.get::<Court>())
+        .map(|court| (court.index, court.ball_speed_scale))
+        .collect();
+    let paddle_ranges: Vec<(usize, (f32, f32))> = {
+        let config = world.resource::<BreakoutConfig>();
+        world
+            .iter_entities()
+            .filter_map(|entity| Some((entity.get::<Paddle>()?, entity.get::<Collider>()?)))
+            .map(|(paddle, collider)| {
+                let half_width = collider.get_half_size().x;
+                (paddle.input, paddle.x_range(config, half_width))
+            })
+            .collect()
+    };
+
+    world.resource_scope(|world, mut bots: Mut<Bots>| {
+        let config = world.resource::<BreakoutConfig>();
+        let ball_speed_scale = world.resource::<BallSpeedScale>().0;
+        let tick_duration = world.resource::<FixedTime>().period.as_secs_f32();
+        let court_size = Vec2::from_array(config.court_size);
+        let demo = bots.demo;
+
+        let mut inputs = Vec::new();
+        for bot in &mut bots.bots {
+            let Some(paddle) = paddles.iter().find(|paddle| paddle.input == bot.input) else {
+                continue;
+            };
+            let paddle_range = paddle_ranges
+                .iter()
+                .find(|(input, _)| *input == bot.input)
+                .map_or((-court_size.x / 2., court_size.x / 2.), |(_, range)| *range);
+            let view = BotView {
+                paddle: paddle.clone(),
+                paddle_range,
+                balls: balls
+                    .iter()
+                    .filter(|ball| ball.court == paddle.court)
+                    .cloned()
+                    .collect(),
+                bricks: bricks
+                    .iter()
+                    .filter(|brick| brick.court == paddle.court)
+                    .cloned()
+                    .collect(),
+                court_size,
+                ball_size: config.ball_size,
+                paddle_speed: config.paddle_speed,
+                ball_speed_scale: ball_speed_scale
+                    * court_speed_scales
+                        .iter()
+                        .find(|(index, _)| *index == paddle.court)
+                        .map_or(1., |(_, scale)| *scale),
+                tick_duration,
+                paddle_bounce: config.paddle_bounce.clone(),
+                angle_multiplier: config.angle_multiplier,
+            };
+
+            let mut input = match (&state, demo) {
+                // The demo picks its game mode the way a player does
+                (BreakoutState::Start, Some(game_mode)) if bot.input == 0 => {
+                    let x = game_mode.zone_center(court_size.x);
+                    PaddleInput {
+                        move_direction: view.move_towards(x),
+                        serve: (x - paddle.position.x).abs() < 1.,
+                    }
+                }
+                (BreakoutState::Start, _) => continue,
+                _ => bot.strategy.input(&view),
+            };
+            input.serve &= !bot.serving;
+            bot.serving = input.serve;
+            inputs.push((bot.input, input));
+        }
+
+        let mut paddle_inputs = world.resource_mut::<PaddleInputs>();
+        for (index, input) in inputs {
+            if let Some(paddle_input) = paddle_inputs.get_mut(index) {
+                *paddle_input = input;
+            }
+        }
+    });
+}
+
+#[derive(Resource, Deref, DerefMut)]
+struct DemoRestartTimer(Timer);
+
+fn start_demo_restart_timer(mut commands: Commands) {
+    commands.insert_resource(DemoRestartTimer(Timer::from_seconds(
+        DEMO_RESTART_DELAY,
+        TimerMode::Once,
+    )));
+}
+
+/// Start the next demo game once the result of the last one has been shown for long enough
+fn restart_demo(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut timer: ResMut<DemoRestartTimer>,
+    mut next_state: ResMut<NextState<BreakoutState>>,
+) {
+    if timer.tick(time.delta()).finished() {
+        commands.remove_resource::<DemoRestartTimer>();
+        next_state.set(BreakoutState::Start);
+    }
+}
+
+/// Bots that play paddles in place of players, picking their inputs every tick
+///
+/// With a demo game mode set, the bot on the first input also starts games by itself, and starts
+/// over a while after a game is over.
+pub(crate) struct AutoplayPlugin;
+
+impl Plugin for AutoplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Bots>()
+            .add_system(
+                bot_inputs
+                    .before(snapshot_inputs)
+                    .in_set(SimulationSet::Input)
+                    .run_if(bots_playing)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                start_demo_restart_timer
+                    .run_if(demo_playing)
+                    .in_schedule(OnEnter(BreakoutState::Finished)),
+            )
+            .add_system(
+                restart_demo
+                    .in_set(OnUpdate(BreakoutState::Finished))
+                    .run_if(resource_exists::<DemoRestartTimer>()),
+            );
+    }
+}
@@ -0,0 +1,75 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Steepest angle away from vertical any bounce model can send the ball at, so the ball never
+/// ends up travelling (almost) horizontally
+const MAX_BOUNCE_ANGLE: f32 = 75. * std::f32::consts::PI / 180.;
+
+/// Exit angles in degrees for each of the eight segments of the classic Atari paddle, from the
+/// left edge to the right edge
+const SEGMENT_ANGLES: [f32; 8] = [60., 45., 30., 15., -15., -30., -45., -60.];
+
+/// How the ball leaves the paddle after hitting it
+#[derive(Reflect, FromReflect, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub(crate) enum PaddleBounce {
+    /// The exit angle grows linearly with the distance from the center of the paddle, scaled by
+    /// `BreakoutConfig::angle_multiplier`
+    #[default]
+    Linear,
+    /// Like `Linear`, but the exit angle never exceeds `max_angle` degrees
+    Clamped { max_angle: f32 },
+    /// Like `Linear`, with the horizontal motion of the paddle at the moment of impact adding
+    /// up to `strength` degrees of spin
+    English { strength: f32 },
+    /// The classic Atari paddle, split into eight segments with a fixed exit angle each
+    Segmented,
+}
+
+impl PaddleBounce {
+    /// The exit angle in radians, measured counterclockwise from straight up
+    ///
+    /// `distance_from_center` is normalized to `[-1, 1]` across the paddle and `paddle_motion`
+    /// is the paddle's horizontal velocity as a fraction of its top speed.
+    pub(crate) fn exit_angle(
+        &self,
+        distance_from_center: f32,
+        paddle_motion: f32,
+        angle_multiplier: f32,
+    ) -> f32 {
+        let distance_from_center = distance_from_center.clamp(-1., 1.);
+        let linear_angle = distance_from_center * angle_multiplier * -FRAC_PI_2;
+
+        let angle = match self {
+            PaddleBounce::Linear => linear_angle,
+            PaddleBounce::Clamped { max_angle } => {
+                let max_angle = max_angle.to_radians();
+                linear_angle.clamp(-max_angle, max_angle)
+            }
+            PaddleBounce::English { strength } => {
+                linear_angle - paddle_motion.clamp(-1., 1.) * strength.to_radians()
+            }
+            PaddleBounce::Segmented => {
+                let segment = ((distance_from_center + 1.) / 2. * SEGMENT_ANGLES.len() as f32)
+                    .floor()
+                    .min(SEGMENT_ANGLES.len() as f32 - 1.) as usize;
+                SEGMENT_ANGLES[segment].to_radians()
+            }
+        };
+
+        angle.clamp(-MAX_BOUNCE_ANGLE, MAX_BOUNCE_ANGLE)
+    }
+
+    /// The velocity of a ball with the given speed leaving the paddle
+    pub(crate) fn exit_velocity(
+        &self,
+        speed: f32,
+        distance_from_center: f32,
+        paddle_motion: f32,
+        angle_multiplier: f32,
+    ) -> Vec2 {
+        let angle = self.exit_angle(distance_from_center, paddle_motion, angle_multiplier);
+        Vec2::from_angle(angle).rotate(Vec2::new(0., 1.)) * speed
+    }
+}
@@ -1,30 +1,59 @@
-use std::f32::consts::PI;
+use std::time::Duration;
 
-use bevy::{
-    prelude::*,
-    sprite::collide_aabb::{collide, Collision},
-};
+use bevy::{prelude::*, reflect::TypeUuid};
+use serde::{Deserialize, Serialize};
 
-use crate::{collision::Collider, util::despawn_with};
+use crate::{
+    bounce::PaddleBounce,
+    collision::{penetration_aabb, sweep_aabb, sweep_inside, Collider, SweepHit},
+    difficulty::DifficultyConfig,
+    level::{
+        advance_level_progress, final_level, level_loaded, reset_level_progress,
+        select_campaign_level, BrickKind, CurrentLevel, Level, LevelPlugin, LevelProgress,
+    },
+    players::{
+        highlight_game_mode, show_game_modes, versus, CourtPlayers, GameModeText, Players,
+        PlayersPlugin, HIGHLIGHT_COLOR,
+    },
+    power_up::PowerUpConfig,
+    rng::GameRng,
+    util::despawn_with,
+    versus::{VersusConfig, VersusPlugin},
+};
 
 pub(crate) const FONT_PATH: &str = "fonts/PublicPixel-z84yD.ttf";
 
-#[derive(Resource, Reflect)]
+/// Tunable parameters of the game, loaded from `config.breakout.ron`
+///
+/// Fields missing from the file keep their default value.
+#[derive(Resource, Reflect, Serialize, Deserialize, TypeUuid, Clone, Debug)]
 #[reflect(Resource)]
+#[serde(default)]
+#[uuid = "b2a19199-f70f-45ce-83e9-b07e17d6c117"]
 pub(crate) struct BreakoutConfig {
     pub(crate) court_size: [f32; 2],
     pub(crate) scale: f32,
     pub(crate) paddle_size: [f32; 2],
     pub(crate) paddle_speed: f32,
     pub(crate) paddle_offset: f32,
+    /// Distance between the rows of stacked paddles
+    pub(crate) stacked_paddle_spacing: f32,
     pub(crate) angle_multiplier: f32,
+    pub(crate) paddle_bounce: PaddleBounce,
     pub(crate) serve_speed: f32,
     pub(crate) serve_offset: f32,
-    pub(crate) num_bricks: [usize; 2],
     pub(crate) bricks_top_offset: f32,
     pub(crate) brick_height: f32,
     pub(crate) brick_padding: f32,
     pub(crate) ball_size: f32,
+    /// Height of the strip at the bottom of the screen where touches are ignored, as a fraction
+    /// of the window height, so the finger doesn't end up covering the paddle
+    pub(crate) touch_dead_zone: f32,
+    pub(crate) power_ups: PowerUpConfig,
+    pub(crate) difficulty: DifficultyConfig,
+    pub(crate) versus: VersusConfig,
+    /// Simulation ticks per second
+    pub(crate) tick_rate: f32,
 }
 
 impl Default for BreakoutConfig {
@@ -35,14 +64,25 @@ impl Default for BreakoutConfig {
             paddle_size: [40., 10.],
             paddle_speed: 1000.,
             paddle_offset: 20.,
+            stacked_paddle_spacing: 60.,
             angle_multiplier: 0.5,
+            paddle_bounce: PaddleBounce::default(),
             serve_speed: 500.,
             serve_offset: 20.,
-            num_bricks: [14, 8],
             bricks_top_offset: 50.,
             brick_height: 10.,
             brick_padding: 2.,
             ball_size: 8.,
+            touch_dead_zone: 0.15,
+            power_ups: PowerUpConfig::default(),
+            difficulty: DifficultyConfig::default(),
+            versus: VersusConfig::default(),
+            // Browsers can't keep up with a 1 kHz simulation
+            tick_rate: if cfg!(target_arch = "wasm32") {
+                120.
+            } else {
+                1000.
+            },
         }
     }
 }
@@ -51,43 +91,176 @@ impl Default for BreakoutConfig {
 pub(crate) enum GameResult {
     Victory,
     GameOver,
+    /// A player won a game of versus, with the index of the winner
+    PlayerWon(usize),
 }
 
 #[derive(States, Default, Debug, Clone, PartialEq, Eq, Hash)]
-pub(crate) enum BreakoutState {
+pub enum BreakoutState {
     #[default]
     Start,
     Serve,
     Playing,
+    /// All bricks of a level are cleared and the next level of the campaign is coming up
+    LevelCleared,
     Finished,
 }
 
+/// A court with walls of its own, side by side with the others in versus
 #[derive(Component)]
-pub(crate) struct Court;
+pub(crate) struct Court {
+    /// Index of the court from the left
+    ///
+    /// The player whose turn it is plays in the first court, and in versus the player with the
+    /// same index plays in each of the others.
+    pub(crate) index: usize,
+    /// Multiplier on how fast the balls in this court travel, raised by the opponent in versus
+    pub(crate) ball_speed_scale: f32,
+}
+
+impl Court {
+    pub(crate) fn new(index: usize) -> Self {
+        Self {
+            index,
+            ball_speed_scale: 1.,
+        }
+    }
+}
 
 #[derive(Component)]
-pub(crate) struct Paddle;
+pub(crate) struct CourtLine;
+
+#[derive(Component, Clone, Debug)]
+pub(crate) struct Paddle {
+    /// Index of the entry of `PaddleInputs` that drives the paddle
+    pub(crate) input: usize,
+    /// Row of the paddle, counting up from the bottom of the court
+    pub(crate) row: usize,
+    /// Part of the court width the paddle moves in, as fractions from the left wall
+    pub(crate) lane: [f32; 2],
+}
+
+impl Default for Paddle {
+    fn default() -> Self {
+        Self {
+            input: 0,
+            row: 0,
+            lane: [0., 1.],
+        }
+    }
+}
+
+impl Paddle {
+    /// Where the paddle starts out, in the middle of its lane
+    pub(crate) fn home_position(&self, config: &BreakoutConfig) -> Vec2 {
+        let lane_center = (self.lane[0] + self.lane[1]) / 2.;
+        Vec2::new(
+            (lane_center - 0.5) * config.court_size[0],
+            -config.court_size[1] / 2.
+                + config.paddle_offset
+                + self.row as f32 * config.stacked_paddle_spacing,
+        )
+    }
+
+    /// Range of the center of the paddle that keeps all of it within its lane
+    pub(crate) fn x_range(&self, config: &BreakoutConfig, half_paddle_width: f32) -> (f32, f32) {
+        let min_x = (self.lane[0] - 0.5) * config.court_size[0] + half_paddle_width;
+        let max_x = (self.lane[1] - 0.5) * config.court_size[0] - half_paddle_width;
+        if min_x <= max_x {
+            (min_x, max_x)
+        } else {
+            let center = (min_x + max_x) / 2.;
+            (center, center)
+        }
+    }
+}
+
+/// Input of the paddle that serves the next ball
+#[derive(Resource, Default)]
+pub(crate) struct ServingPaddle(pub(crate) usize);
 
 #[derive(Component)]
 pub(crate) struct Ball;
 
+/// A ball waiting on top of a paddle for its player to serve
+#[derive(Component)]
+pub(crate) struct HeldBall {
+    pub(crate) paddle: Entity,
+}
+
+/// Position within the court as seen by the simulation
+///
+/// The `Transform` of simulated entities is only used for drawing, and follows this position.
+#[derive(Component, Deref, DerefMut, Default, Reflect)]
+pub(crate) struct Position(pub(crate) Vec2);
+
+/// Velocity in world units per second
 #[derive(Component, Deref, DerefMut, Default, Reflect)]
 pub(crate) struct Velocity(pub(crate) Vec2);
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub(crate) struct Brick {
     pub(crate) points: u32,
+    /// Top row of the brick on the level grid
+    pub(crate) row: usize,
+    pub(crate) kind: BrickKind,
+    /// Hits left before the brick is destroyed
+    pub(crate) hits_left: u32,
+}
+
+/// The color of an undamaged brick
+#[derive(Component)]
+pub(crate) struct BrickColor(pub(crate) Color);
+
+/// Width of the line around the court
+const COURT_LINE_WIDTH: f32 = 5.;
+
+/// Space between courts that are side by side
+const COURT_GAP: f32 = 40.;
+
+/// Size of the area taken up by `court_count` courts side by side
+pub(crate) fn courts_size(config: &BreakoutConfig, court_count: usize) -> Vec2 {
+    let court_count = court_count.max(1) as f32;
+    Vec2::new(
+        config.court_size[0] * court_count + COURT_GAP * (court_count - 1.),
+        config.court_size[1],
+    )
 }
+
+/// Where the court with this index goes, with the courts centered on the screen together
+pub(crate) fn court_translation(config: &BreakoutConfig, index: usize, court_count: usize) -> Vec3 {
+    let spacing = config.court_size[0] + COURT_GAP;
+    let x = (index as f32 - (court_count.max(1) - 1) as f32 / 2.) * spacing;
+    Vec3::new(x, -30., 1.)
+}
+
 pub(crate) fn setup_court(mut commands: Commands, config: Res<BreakoutConfig>) {
-    let line_width = 5.;
+    let court_entity = spawn_court(&mut commands, &config, 0, 1);
+    commands.entity(court_entity).with_children(|parent| {
+        spawn_paddle(parent, &config, Paddle::default());
+    });
+}
+
+/// Spawn an empty court, out of `court_count` courts side by side
+pub(crate) fn spawn_court(
+    commands: &mut Commands,
+    config: &BreakoutConfig,
+    index: usize,
+    court_count: usize,
+) -> Entity {
+    let line_width = COURT_LINE_WIDTH;
     commands
         // Spawn court
         .spawn((
-            Court,
+            Court::new(index),
             Name::new("Court"),
             Collider::new(config.court_size[0], config.court_size[1]),
             SpriteBundle {
-                transform: Transform::from_translation(Vec3::new(0., -30., 1.)),
+                transform: Transform::from_translation(court_translation(
+                    config,
+                    index,
+                    court_count,
+                )),
                 sprite: Sprite {
                     color: Color::BLACK,
                     custom_size: Some(Vec2::new(config.court_size[0], config.court_size[1])),
@@ -99,6 +272,7 @@ pub(crate) fn setup_court(mut commands: Commands, config: Res<BreakoutConfig>) {
         .with_children(|parent| {
             // Spawn courtline
             parent.spawn((
+                CourtLine,
                 Name::new("Court line"),
                 SpriteBundle {
                     sprite: Sprite {
@@ -113,44 +287,103 @@ pub(crate) fn setup_court(mut commands: Commands, config: Res<BreakoutConfig>) {
                     ..default()
                 },
             ));
+        })
+        .id()
+}
 
-            // Spawn paddle
-            parent.spawn((
-                Paddle,
-                Name::new("Paddle"),
-                Collider::new(config.paddle_size[0], config.paddle_size[1]),
-                SpriteBundle {
-                    transform: Transform::from_translation(Vec3::new(
-                        0.,
-                        -config.court_size[1] / 2. + config.paddle_offset,
-                        1.,
-                    )),
-                    sprite: Sprite {
-                        color: Color::WHITE,
-                        custom_size: Some(Vec2::from_array(config.paddle_size)),
-                        ..default()
-                    },
-                    ..default()
-                },
-            ));
-        });
+pub(crate) fn spawn_paddle(parent: &mut ChildBuilder, config: &BreakoutConfig, paddle: Paddle) {
+    let position = paddle.home_position(config);
+    let size = Vec2::from_array(config.paddle_size);
+    parent.spawn((
+        paddle,
+        Name::new("Paddle"),
+        Position(position),
+        Velocity::default(),
+        Collider::new(size.x, size.y),
+        SpriteBundle {
+            transform: Transform::from_translation(position.extend(1.)),
+            sprite: Sprite {
+                color: Color::WHITE,
+                custom_size: Some(size),
+                ..default()
+            },
+            ..default()
+        },
+    ));
 }
 
+/// The paddle in a court that serves, or any paddle in the court if it is missing
+pub(crate) fn serving_paddle_in_court(
+    serving_paddle: &ServingPaddle,
+    court: Entity,
+    paddle_query: &Query<(Entity, &Paddle, &Position, &Parent)>,
+) -> Option<(Entity, Vec2)> {
+    let court_paddles = || {
+        paddle_query
+            .iter()
+            .filter(move |(.., parent)| parent.get() == court)
+    };
+    court_paddles()
+        .find(|(_, paddle, ..)| paddle.input == serving_paddle.0)
+        .or_else(|| court_paddles().next())
+        .map(|(entity, _, position, _)| (entity, position.0))
+}
+
+/// Spawn the ball that is served in every court
 fn spawn_ball(
     mut commands: Commands,
     config: Res<BreakoutConfig>,
-    paddle_query: Query<Entity, With<Paddle>>,
+    court_query: Query<Entity, With<Court>>,
+    serving_paddle: Res<ServingPaddle>,
+    paddle_query: Query<(Entity, &Paddle, &Position, &Parent)>,
 ) {
-    let paddle_entity = paddle_query.single();
-    commands.entity(paddle_entity).with_children(|parent| {
-        // Spawn ball
-        parent.spawn((
+    for court_entity in &court_query {
+        spawn_held_ball(
+            &mut commands,
+            &config,
+            &serving_paddle,
+            court_entity,
+            &paddle_query,
+        );
+    }
+}
+
+/// Spawn a ball in a court, held on its serving paddle until its player serves
+pub(crate) fn spawn_held_ball(
+    commands: &mut Commands,
+    config: &BreakoutConfig,
+    serving_paddle: &ServingPaddle,
+    court: Entity,
+    paddle_query: &Query<(Entity, &Paddle, &Position, &Parent)>,
+) {
+    let Some((paddle_entity, paddle_position)) =
+        serving_paddle_in_court(serving_paddle, court, paddle_query)
+    else {
+        return;
+    };
+    let ball_position = paddle_position + Vec2::new(0., config.serve_offset);
+    let ball_entity = spawn_ball_in_court(commands, config, court, ball_position, Vec2::ZERO);
+    commands.entity(ball_entity).insert(HeldBall {
+        paddle: paddle_entity,
+    });
+}
+
+fn spawn_ball_in_court(
+    commands: &mut Commands,
+    config: &BreakoutConfig,
+    court: Entity,
+    position: Vec2,
+    velocity: Vec2,
+) -> Entity {
+    let ball_entity = commands
+        .spawn((
             Ball,
             Name::new("Ball"),
-            Velocity::default(),
+            Position(position),
+            Velocity(velocity),
             Collider::new(config.ball_size, config.ball_size),
             SpriteBundle {
-                transform: Transform::from_translation(Vec3::new(0.0, config.serve_offset, 0.)),
+                transform: Transform::from_translation(position.extend(1.)),
                 sprite: Sprite {
                     color: Color::WHITE,
                     custom_size: Some(Vec2::new(config.ball_size, config.ball_size)),
@@ -158,217 +391,706 @@ fn spawn_ball(
                 },
                 ..default()
             },
-        ));
-    });
+        ))
+        .id();
+    commands.entity(court).add_child(ball_entity);
+    ball_entity
+}
+
+/// Sent to split every ball in play into `balls` balls
+pub(crate) struct SplitBallsEvent {
+    pub(crate) balls: usize,
+}
+
+/// Angle in degrees between the balls a ball is split into
+const SPLIT_BALL_SPREAD: f32 = 20.;
+
+/// Split every moving ball into several balls fanning out from its direction of travel, in the
+/// court the ball is in
+///
+/// Balls that aren't moving, like those held on a paddle, are left alone.
+pub(crate) fn split_balls(
+    mut commands: Commands,
+    config: Res<BreakoutConfig>,
+    mut split_balls_events: EventReader<SplitBallsEvent>,
+    ball_query: Query<(Entity, &Position, &Velocity, &Parent), With<Ball>>,
+) {
+    let mut ball_entities: Vec<Entity> = ball_query.iter().map(|(entity, ..)| entity).collect();
+    ball_entities.sort();
+    // Spawned balls only show up in the query next tick, so keep track of them here for
+    // several splits in one tick to add up
+    let mut balls: Vec<(Entity, Vec2, Vec2)> = ball_entities
+        .into_iter()
+        .map(|entity| {
+            let (_, position, velocity, parent) = ball_query.get(entity).unwrap();
+            (parent.get(), position.0, velocity.0)
+        })
+        .filter(|(.., velocity)| *velocity != Vec2::ZERO)
+        .collect();
+
+    for SplitBallsEvent { balls: count } in split_balls_events.iter() {
+        let mut new_balls = Vec::new();
+        for (court, position, velocity) in &balls {
+            for index in 1..*count {
+                // Alternate sides, fanning out further with every pair
+                let side = if index % 2 == 1 { 1. } else { -1. };
+                let angle = side * ((index + 1) / 2) as f32 * SPLIT_BALL_SPREAD.to_radians();
+                new_balls.push((*court, *position, Vec2::from_angle(angle).rotate(*velocity)));
+            }
+        }
+
+        for (court, position, velocity) in new_balls {
+            spawn_ball_in_court(&mut commands, &config, court, position, velocity);
+            balls.push((court, position, velocity));
+        }
+    }
 }
 
-fn spawn_bricks(
+/// Fill every court with the bricks of the current level
+pub(crate) fn spawn_bricks(
     mut commands: Commands,
     config: Res<BreakoutConfig>,
+    current_level: Res<CurrentLevel>,
+    levels: Res<Assets<Level>>,
     court_query: Query<Entity, With<Court>>,
+) {
+    let level = levels
+        .get(&current_level.0)
+        .expect("bricks are only spawned once the level is loaded");
+    info!("Starting level {}", level.name);
+
+    for court_entity in &court_query {
+        spawn_level_bricks(&mut commands, &config, level, court_entity);
+    }
+}
+
+/// The parameters of `BreakoutConfig` that determine where bricks are placed
+fn brick_layout(config: &BreakoutConfig) -> [f32; 5] {
+    [
+        config.court_size[0],
+        config.court_size[1],
+        config.bricks_top_offset,
+        config.brick_height,
+        config.brick_padding,
+    ]
+}
+
+/// Respawn the bricks when the current level file or the brick layout parameters change during
+/// play, leaving the score and lives alone
+fn respawn_changed_bricks(
+    mut commands: Commands,
+    config: Res<BreakoutConfig>,
+    current_level: Res<CurrentLevel>,
+    levels: Res<Assets<Level>>,
+    state: Res<State<BreakoutState>>,
+    mut level_events: EventReader<AssetEvent<Level>>,
+    mut previous_layout: Local<Option<[f32; 5]>>,
+    court_query: Query<Entity, With<Court>>,
+    brick_query: Query<Entity, With<Brick>>,
+) {
+    let level_modified = level_events.iter().any(
+        |event| matches!(event, AssetEvent::Modified { handle } if *handle == current_level.0),
+    );
+    let layout = brick_layout(&config);
+    let layout_changed = previous_layout.map_or(false, |previous| previous != layout);
+    *previous_layout = Some(layout);
+
+    // Bricks are only around after leaving the start state, and until a level is cleared
+    let bricks_spawned = !matches!(state.0, BreakoutState::Start | BreakoutState::LevelCleared);
+    if !bricks_spawned || !(level_modified || layout_changed) {
+        return;
+    }
+    let Some(level) = levels.get(&current_level.0) else {
+        return;
+    };
+
+    info!("Reloading bricks of level {}", level.name);
+    for brick_entity in &brick_query {
+        commands.entity(brick_entity).despawn_recursive();
+    }
+    for court_entity in &court_query {
+        spawn_level_bricks(&mut commands, &config, level, court_entity);
+    }
+}
+
+pub(crate) fn spawn_level_bricks(
+    commands: &mut Commands,
+    config: &BreakoutConfig,
+    level: &Level,
+    court: Entity,
 ) {
     // Spawn bricks
-    let brick_size_with_padding = [
-        config.court_size[0] / config.num_bricks[0] as f32,
+    let cell_size = Vec2::new(
+        config.court_size[0] / level.columns as f32,
         config.brick_height,
-    ];
+    );
 
-    let court = court_query.single();
     commands.entity(court).with_children(|parent| {
-        let brick_colors = [Color::RED, Color::ORANGE, Color::GREEN, Color::YELLOW];
-        for x in 0..config.num_bricks[0] {
-            for y in 0..config.num_bricks[1] {
-                {
-                    let brick_width = brick_size_with_padding[0] - config.brick_padding / 2.;
-                    let brick_height = brick_size_with_padding[1] - config.brick_padding / 2.;
-                    parent.spawn((
-                        Brick {
-                            points: (config.num_bricks[1] - y) as u32,
-                        },
-                        Name::new("Brick"),
-                        Collider::new(brick_width, brick_height),
-                        SpriteBundle {
-                            transform: Transform::from_translation(Vec3::new(
-                                -config.court_size[0] / 2.
-                                    + brick_size_with_padding[0] / 2.
-                                    + x as f32 * brick_size_with_padding[0],
-                                config.court_size[1] / 2.
-                                    - brick_size_with_padding[1] / 2.
-                                    - y as f32 * brick_size_with_padding[1]
-                                    - config.bricks_top_offset,
-                                1.,
-                            )),
-                            sprite: Sprite {
-                                color: brick_colors[y / 2],
-                                custom_size: Some(Vec2::new(brick_width, brick_height)),
-                                ..default()
-                            },
-                            ..default()
-                        },
-                    ));
-                }
-            }
+        for level_brick in &level.bricks {
+            let brick = Brick {
+                points: level_brick.points,
+                row: level_brick.row,
+                kind: level_brick.kind,
+                hits_left: level_brick.kind.hits(),
+            };
+
+            let size_with_padding =
+                cell_size * Vec2::new(level_brick.width as f32, level_brick.height as f32);
+            let position = Vec2::new(
+                -config.court_size[0] / 2.
+                    + size_with_padding.x / 2.
+                    + level_brick.column as f32 * cell_size.x,
+                config.court_size[1] / 2.
+                    - size_with_padding.y / 2.
+                    - level_brick.row as f32 * cell_size.y
+                    - config.bricks_top_offset,
+            );
+            let size = size_with_padding - config.brick_padding / 2.;
+            spawn_brick(parent, brick, level_brick.color, position, size);
         }
     });
 }
 
-#[derive(Default)]
-pub(crate) struct PaddleInput {
-    pub(crate) move_direction: f32,
-    pub(crate) serve: bool,
+pub(crate) fn spawn_brick(
+    parent: &mut ChildBuilder,
+    brick: Brick,
+    color: Color,
+    position: Vec2,
+    size: Vec2,
+) {
+    parent.spawn((
+        brick,
+        BrickColor(color),
+        Name::new("Brick"),
+        Collider::new(size.x, size.y),
+        SpriteBundle {
+            transform: Transform::from_translation(position.extend(1.)),
+            sprite: Sprite {
+                color,
+                custom_size: Some(size),
+                ..default()
+            },
+            ..default()
+        },
+    ));
 }
 
+/// What a player does with their paddle during a tick
+#[derive(Default, Clone, Debug)]
+pub struct PaddleInput {
+    /// How fast the paddle moves, as a multiple of the configured paddle speed, negative to the
+    /// left
+    pub move_direction: f32,
+    /// Whether serve is held
+    pub serve: bool,
+}
+
+/// The latest inputs for every paddle, written by the input backends
 #[derive(Resource, Deref, DerefMut)]
 pub(crate) struct PaddleInputs(pub(crate) Vec<PaddleInput>);
 
+/// The snapshot of `PaddleInputs` the simulation works with during a single tick
+#[derive(Resource, Deref, DerefMut, Default)]
+pub(crate) struct TickInputs(pub(crate) Vec<PaddleInput>);
+
+/// The inputs of the previous tick, to tell when a button goes down
+#[derive(Resource, Deref, DerefMut, Default)]
+pub(crate) struct PreviousTickInputs(pub(crate) Vec<PaddleInput>);
+
+pub(crate) fn snapshot_inputs(
+    inputs: Res<PaddleInputs>,
+    mut tick_inputs: ResMut<TickInputs>,
+    mut previous_tick_inputs: ResMut<PreviousTickInputs>,
+) {
+    std::mem::swap(&mut previous_tick_inputs.0, &mut tick_inputs.0);
+    tick_inputs.0.clone_from(&inputs.0);
+}
+
+/// Whether serve is held on the input with this index
+pub(crate) fn serve_pressed_on(tick_inputs: &TickInputs, input: usize) -> bool {
+    tick_inputs.get(input).map_or(false, |input| input.serve)
+}
+
+/// Whether serve went down this tick on the input with this index
+pub(crate) fn serve_just_pressed_on(
+    tick_inputs: &TickInputs,
+    previous_tick_inputs: &PreviousTickInputs,
+    input: usize,
+) -> bool {
+    serve_pressed_on(tick_inputs, input)
+        && !previous_tick_inputs
+            .get(input)
+            .map_or(false, |input| input.serve)
+}
+
+/// Whether serve went down this tick on any input
+pub(crate) fn serve_just_pressed(
+    tick_inputs: Res<TickInputs>,
+    previous_tick_inputs: Res<PreviousTickInputs>,
+) -> bool {
+    (0..tick_inputs.len())
+        .any(|input| serve_just_pressed_on(&tick_inputs, &previous_tick_inputs, input))
+}
+
+/// Whether serve has been let go of on every input since entering `Start`, so the press that
+/// restarted the game doesn't also pick the game mode
+#[derive(Resource, Deref, DerefMut, Default)]
+pub(crate) struct ServeReleased(pub(crate) bool);
+
+pub(crate) fn reset_serve_released(mut serve_released: ResMut<ServeReleased>) {
+    serve_released.0 = false;
+}
+
+fn track_serve_release(tick_inputs: Res<TickInputs>, mut serve_released: ResMut<ServeReleased>) {
+    if !tick_inputs.iter().any(|input| input.serve) {
+        serve_released.0 = true;
+    }
+}
+
+fn serve_released(serve_released: Res<ServeReleased>) -> bool {
+    serve_released.0
+}
+
+/// Multiplier on the configured paddle width
+#[derive(Resource, Deref, DerefMut)]
+pub(crate) struct PaddleWidthScale(pub(crate) f32);
+
+impl Default for PaddleWidthScale {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+/// Multiplier on how fast the balls travel, without touching their velocity
+#[derive(Resource, Deref, DerefMut)]
+pub(crate) struct BallSpeedScale(pub(crate) f32);
+
+impl Default for BallSpeedScale {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+/// Keep held balls on top of their paddle while waiting for the serve
+pub(crate) fn hold_ball_on_paddle(
+    config: Res<BreakoutConfig>,
+    mut ball_query: Query<(&HeldBall, &mut Position), Without<Paddle>>,
+    paddle_query: Query<&Position, With<Paddle>>,
+) {
+    for (held_ball, mut ball_position) in &mut ball_query {
+        if let Ok(paddle_position) = paddle_query.get(held_ball.paddle) {
+            ball_position.0 = paddle_position.0 + Vec2::new(0., config.serve_offset);
+        }
+    }
+}
+
+/// Send held balls off once the player of their paddle presses serve, which starts play
 pub(crate) fn serve(
     mut commands: Commands,
-    fixed_timestep: Res<FixedTime>,
     config: Res<BreakoutConfig>,
-    mut ball_query: Query<(Entity, &mut Transform, &mut Velocity), (With<Ball>, Without<Paddle>)>,
-    court_query: Query<Entity, With<Court>>,
-    mut paddle_query: Query<&mut Transform, With<Paddle>>,
+    tick_inputs: Res<TickInputs>,
+    previous_tick_inputs: Res<PreviousTickInputs>,
+    state: Res<State<BreakoutState>>,
+    mut ball_query: Query<(Entity, &HeldBall, &mut Velocity)>,
+    paddle_query: Query<&Paddle>,
     mut next_state: ResMut<NextState<BreakoutState>>,
 ) {
-    let (ball_entity, mut ball_transform, mut ball_velocity) = ball_query.single_mut();
-    let paddle_translation = paddle_query.single_mut().translation;
-    let court_entity = court_query.single();
-    commands.entity(court_entity).add_child(ball_entity);
-    let current_timestep = fixed_timestep.period.as_secs_f32();
-    ball_velocity.0 = config.serve_speed * current_timestep * Vec2::new(0., 1.).normalize();
-    ball_transform.translation = Vec3::new(
-        paddle_translation.x,
-        paddle_translation.y + config.serve_offset,
-        1.,
-    );
-    next_state.set(BreakoutState::Playing);
+    for (ball_entity, held_ball, mut ball_velocity) in &mut ball_query {
+        let Ok(paddle) = paddle_query.get(held_ball.paddle) else {
+            continue;
+        };
+        // A serve held since the game started doesn't count
+        if !serve_just_pressed_on(&tick_inputs, &previous_tick_inputs, paddle.input) {
+            continue;
+        }
+
+        ball_velocity.0 = config.serve_speed * Vec2::new(0., 1.).normalize();
+        commands.entity(ball_entity).remove::<HeldBall>();
+        if state.0 == BreakoutState::Serve {
+            next_state.set(BreakoutState::Playing);
+        }
+    }
 }
 
 pub(crate) fn paddle_movement(
-    time: Res<Time>,
-    mut paddle_query: Query<&mut Transform, With<Paddle>>,
+    fixed_time: Res<FixedTime>,
+    mut paddle_query: Query<(&Paddle, &mut Position, &mut Velocity, &Collider)>,
     config: Res<BreakoutConfig>,
-    inputs: Res<PaddleInputs>,
+    inputs: Res<TickInputs>,
 ) {
-    let half_court_width = config.court_size[0] / 2.;
-    let half_paddle_width = config.paddle_size[0] / 2.;
+    let delta_seconds = fixed_time.period.as_secs_f32();
 
-    for mut transform in &mut paddle_query {
-        let input = &inputs[0];
+    for (paddle, mut position, mut velocity, collider) in &mut paddle_query {
+        let move_direction = inputs
+            .get(paddle.input)
+            .map_or(0., |input| input.move_direction);
+        let (min_x, max_x) = paddle.x_range(&config, collider.get_half_size().x);
 
-        let translation = &mut transform.translation;
-        translation.x += input.move_direction * config.paddle_speed * time.delta_seconds();
-        translation.x = translation.x.clamp(
-            -half_court_width + half_paddle_width,
-            half_court_width - half_paddle_width,
-        );
+        let previous_x = position.x;
+        position.x += move_direction * config.paddle_speed * delta_seconds;
+        position.x = position.x.clamp(min_x, max_x);
+
+        // Keep track of how fast the paddle actually moved, for bounce models that use it
+        velocity.x = (position.x - previous_x) / delta_seconds;
     }
 }
 
+/// Sent when the ball, a laser or an explosion hits a brick
 pub(crate) struct BrickCollisionEvent {
-    brick_entity: Entity,
+    pub(crate) brick_entity: Entity,
 }
 
+/// Sent when a ball leaves the court through the bottom
 #[derive(Debug, Clone)]
-pub(crate) struct BottomCollisionEvent;
+pub(crate) struct BottomCollisionEvent {
+    pub(crate) ball_entity: Entity,
+}
+
+/// Sent when a ball bounces off a paddle
+pub(crate) struct PaddleCollisionEvent {
+    pub(crate) ball_entity: Entity,
+    pub(crate) paddle_entity: Entity,
+}
+
+/// Sent when a ball bounces off one of the walls of the court
+pub(crate) struct WallCollisionEvent {
+    /// Normal of the wall, pointing into the court
+    pub(crate) normal: Vec2,
+}
+
+/// Sent when a life is lost
+pub(crate) struct LifeLostEvent;
+
+/// Maximum number of contacts resolved for a single ball in one tick
+const MAX_BALL_CONTACTS: usize = 8;
+
+/// Contacts this close together in time are resolved as a single simultaneous contact
+const SIMULTANEOUS_CONTACT_EPSILON: f32 = 1e-4;
+
+/// What the ball ran into while sweeping along its movement
+#[derive(Clone, Copy)]
+enum ContactTarget {
+    Wall,
+    Bottom,
+    Paddle {
+        entity: Entity,
+        position: Vec2,
+        size: Vec2,
+        velocity: Vec2,
+    },
+    Brick {
+        entity: Entity,
+        position: Vec2,
+    },
+}
+
+#[derive(Clone, Copy)]
+struct BallContact {
+    hit: SweepHit,
+    target: ContactTarget,
+    /// How deep the ball already is inside the target, for contacts that start out overlapping
+    penetration: f32,
+}
+
+/// Collect every contact of a ball moving by `displacement` through `court`, including colliders
+/// it is already inside of.
+fn find_ball_contacts(
+    ball_position: Vec2,
+    ball_half_size: Vec2,
+    displacement: Vec2,
+    court: Entity,
+    court_half_size: Vec2,
+    paddle_query: &Query<(Entity, &Position, &Collider, &Velocity, &Parent), With<Paddle>>,
+    brick_query: &Query<(Entity, &Transform, &Collider, &Parent), With<Brick>>,
+    hit_bricks: &[Entity],
+) -> Vec<BallContact> {
+    let mut contacts = Vec::new();
+
+    if let Some(hit) = sweep_inside(ball_position, ball_half_size, displacement, court_half_size) {
+        let target = if hit.normal.y > 0. {
+            ContactTarget::Bottom
+        } else {
+            ContactTarget::Wall
+        };
+        contacts.push(BallContact {
+            hit,
+            target,
+            penetration: 0.,
+        });
+    }
+
+    let mut add_box_contact = |target_position: Vec2, target_half_size: Vec2, target| {
+        if let Some((normal, penetration)) = penetration_aabb(
+            ball_position,
+            ball_half_size,
+            target_position,
+            target_half_size,
+        ) {
+            // Rounding can leave a ball that just bounced off a face a hair inside of it, which
+            // is no contact as long as the ball is on its way out
+            if displacement.dot(normal) < 0. {
+                contacts.push(BallContact {
+                    hit: SweepHit { time: 0., normal },
+                    target,
+                    penetration,
+                });
+            }
+        } else if let Some(hit) = sweep_aabb(
+            ball_position,
+            ball_half_size,
+            displacement,
+            target_position,
+            target_half_size,
+        ) {
+            contacts.push(BallContact {
+                hit,
+                target,
+                penetration: 0.,
+            });
+        }
+    };
+
+    // Paddles only catch balls on their way down, so balls pass up through stacked paddles
+    if displacement.y < 0. {
+        for (paddle_entity, paddle_position, paddle_collider, paddle_velocity, parent) in
+            paddle_query
+        {
+            if parent.get() != court {
+                continue;
+            }
+            add_box_contact(
+                paddle_position.0,
+                paddle_collider.get_half_size(),
+                ContactTarget::Paddle {
+                    entity: paddle_entity,
+                    position: paddle_position.0,
+                    size: paddle_collider.get_size(),
+                    velocity: paddle_velocity.0,
+                },
+            );
+        }
+    }
+
+    for (brick_entity, brick_transform, brick_collider, parent) in brick_query {
+        if parent.get() != court || hit_bricks.contains(&brick_entity) {
+            continue;
+        }
+
+        add_box_contact(
+            brick_transform.translation.truncate(),
+            brick_collider.get_half_size(),
+            ContactTarget::Brick {
+                entity: brick_entity,
+                position: brick_transform.translation.truncate(),
+            },
+        );
+    }
+
+    contacts
+}
+
+/// Combine the normals of simultaneous wall and brick contacts into the single normal the ball
+/// is reflected off.
+///
+/// The axis with the largest combined normal wins, and an exact tie is treated as a corner that
+/// reflects both axes.
+fn dominant_normal(contacts: &[BallContact], velocity: Vec2) -> Vec2 {
+    let sum: Vec2 = contacts.iter().map(|contact| contact.hit.normal).sum();
+
+    if sum.x.abs() > sum.y.abs() {
+        Vec2::new(sum.x.signum(), 0.)
+    } else if sum.y.abs() > sum.x.abs() {
+        Vec2::new(0., sum.y.signum())
+    } else if sum != Vec2::ZERO {
+        Vec2::new(sum.x.signum(), sum.y.signum())
+    } else {
+        // Opposing contacts cancel out, bounce back along the main axis of movement
+        if velocity.x.abs() > velocity.y.abs() {
+            Vec2::new(-velocity.x.signum(), 0.)
+        } else {
+            Vec2::new(0., -velocity.y.signum())
+        }
+    }
+}
 
 pub(crate) fn ball_movement(
-    court_query: Query<&Collider, With<Court>>,
+    court_query: Query<(&Court, &Collider)>,
     mut ball_query: Query<
-        (&mut Transform, &mut Velocity, &Collider),
+        (Entity, &mut Position, &mut Velocity, &Collider, &Parent),
         (With<Ball>, Without<Paddle>, Without<Brick>, Without<Court>),
     >,
-    paddle_query: Query<(&Transform, &Collider), With<Paddle>>,
-    brick_query: Query<(Entity, &Transform, &Collider), With<Brick>>,
+    paddle_query: Query<(Entity, &Position, &Collider, &Velocity, &Parent), With<Paddle>>,
+    brick_query: Query<(Entity, &Transform, &Collider, &Parent), With<Brick>>,
+    config: Res<BreakoutConfig>,
+    fixed_time: Res<FixedTime>,
+    ball_speed_scale: Res<BallSpeedScale>,
     mut brick_collision_events: EventWriter<BrickCollisionEvent>,
     mut bottom_collision_events: EventWriter<BottomCollisionEvent>,
+    mut paddle_collision_events: EventWriter<PaddleCollisionEvent>,
+    mut wall_collision_events: EventWriter<WallCollisionEvent>,
 ) {
-    let court_collider = court_query.single();
-
-    for (mut ball_transform, mut ball_velocity, ball_collider) in &mut ball_query {
-        let ball_translation = &mut ball_transform.translation;
-        ball_translation.x += ball_velocity.x;
-        ball_translation.y += ball_velocity.y;
-
-        if let Some(collision) = collide(
-            *ball_translation,
-            ball_collider.get_size(),
-            Vec3::ZERO,
-            court_collider.get_size(),
-        ) {
-            match collision {
-                Collision::Left | Collision::Right => {
-                    ball_velocity.x = -ball_velocity.x;
-                }
-                Collision::Top => {
-                    ball_velocity.y = -ball_velocity.y;
-                }
-                Collision::Bottom => {
-                    // Send a BottomCollisionEvent and reset the ball
-                    bottom_collision_events.send(BottomCollisionEvent);
-                }
-                _ => {}
-            };
+    let mut hit_bricks = Vec::new();
+
+    // Resolve balls in a fixed order so that shared bricks always go to the same ball
+    let mut ball_entities: Vec<Entity> = ball_query.iter().map(|(entity, ..)| entity).collect();
+    ball_entities.sort();
+
+    for ball_entity in ball_entities {
+        let (_, mut position, mut ball_velocity, ball_collider, parent) =
+            ball_query.get_mut(ball_entity).unwrap();
+        let court_entity = parent.get();
+        let Ok((court, court_collider)) = court_query.get(court_entity) else {
+            continue;
         };
+        let court_half_size = court_collider.get_half_size();
+        let delta_seconds =
+            fixed_time.period.as_secs_f32() * ball_speed_scale.0 * court.ball_speed_scale;
+        let ball_half_size = ball_collider.get_half_size();
+        let mut ball_position = position.0;
+        // Fraction of this tick's movement that is still left to travel
+        let mut remaining = 1.;
 
-        // Check for paddle collision
-        for (paddle_transform, paddle_collider) in &paddle_query {
-            let half_ball_size = ball_collider.get_half_size().x;
-            let paddle_collider_size = paddle_collider.get_size();
-            let paddle_translation = &paddle_transform.translation;
-            let paddle_half_width = paddle_collider_size.x / 2.;
-            let paddle_half_height = paddle_collider_size.y / 2.;
-            let paddle_top = paddle_translation.y + paddle_half_height;
-
-            if let Some(_collision) = collide(
-                *ball_translation,
-                ball_collider.get_size(),
-                *paddle_translation,
-                paddle_collider.get_size(),
-            ) {
-                // Hit the paddle
-                ball_velocity.y = -ball_velocity.y;
-                // the distance from the center of the paddle, normalized to [-1, 1]
-                let distance_from_center = (ball_translation.x - paddle_translation.x)
-                    / (paddle_half_width + half_ball_size);
-                // the angle of the ball velocity, based on the distance from the center
-                let angle = distance_from_center * 0.25 * -PI;
-                // rotate the ball velocity by the angle
-                ball_velocity.0 =
-                    Vec2::new(0., 1.).rotate(Vec2::from_angle(angle)) * ball_velocity.0.length();
+        for _ in 0..MAX_BALL_CONTACTS {
+            let displacement = ball_velocity.0 * delta_seconds * remaining;
 
-                // translate the ball back so it's not inside the paddle
-                ball_translation.y = paddle_top + half_ball_size + 0.1;
+            let mut contacts = find_ball_contacts(
+                ball_position,
+                ball_half_size,
+                displacement,
+                court_entity,
+                court_half_size,
+                &paddle_query,
+                &brick_query,
+                &hit_bricks,
+            );
+
+            // Only the earliest contacts along the movement segment are resolved this step
+            let Some(first_time) = contacts
+                .iter()
+                .map(|contact| contact.hit.time)
+                .reduce(f32::min)
+            else {
+                ball_position += displacement;
+                break;
+            };
+            contacts
+                .retain(|contact| contact.hit.time <= first_time + SIMULTANEOUS_CONTACT_EPSILON);
+
+            // Move up to the contact and resolve it, then continue with the remaining motion
+            ball_position += displacement * first_time;
+            remaining *= 1. - first_time;
+
+            if contacts
+                .iter()
+                .any(|contact| matches!(contact.target, ContactTarget::Bottom))
+            {
+                // The ball is out, the rules take it from here
+                bottom_collision_events.send(BottomCollisionEvent { ball_entity });
+                break;
             }
-        }
 
-        let mut new_velocity = ball_velocity.0;
+            // Of several paddles, the one closest to the ball takes the hit
+            let paddle_contact = contacts
+                .iter()
+                .filter_map(|contact| match contact.target {
+                    ContactTarget::Paddle {
+                        entity,
+                        position,
+                        size,
+                        velocity,
+                    } => Some((entity, position, size, velocity)),
+                    _ => None,
+                })
+                .min_by(|(_, a, ..), (_, b, ..)| {
+                    (a.x - ball_position.x)
+                        .abs()
+                        .total_cmp(&(b.x - ball_position.x).abs())
+                });
 
-        // Check for brick collision
-        for (brick_entity, brick_transform, brick_collider) in &brick_query {
-            let brick_translation = &brick_transform.translation;
+            // Walls and bricks hit at the same time as the paddle are resolved all the same, and
+            // with only the paddle hit there is nothing to reflect off before it
+            contacts.retain(|contact| !matches!(contact.target, ContactTarget::Paddle { .. }));
+            let normal = if contacts.is_empty() {
+                Vec2::ZERO
+            } else {
+                dominant_normal(&contacts, ball_velocity.0)
+            };
+
+            for contact in &contacts {
+                if matches!(contact.target, ContactTarget::Wall) {
+                    wall_collision_events.send(WallCollisionEvent {
+                        normal: contact.hit.normal,
+                    });
+                }
+            }
 
-            if let Some(collision) = collide(
-                *ball_translation,
-                ball_collider.get_size(),
-                *brick_translation,
-                brick_collider.get_size(),
-            ) {
+            // Push the ball out of anything it started inside of
+            let penetration = contacts
+                .iter()
+                .map(|contact| contact.penetration)
+                .fold(0., f32::max);
+            ball_position += normal * penetration;
+
+            // Every brick that was hit on a face agreeing with the dominant normal is destroyed,
+            // in a stable order so the outcome doesn't depend on query iteration order
+            let mut destroyed_bricks: Vec<(Entity, Vec2)> = contacts
+                .iter()
+                .filter_map(|contact| match contact.target {
+                    ContactTarget::Brick { entity, position }
+                        if contact.hit.normal.dot(normal) > 0. =>
+                    {
+                        Some((entity, position))
+                    }
+                    _ => None,
+                })
+                .collect();
+            destroyed_bricks
+                .sort_by(|(_, a), (_, b)| b.y.total_cmp(&a.y).then(a.x.total_cmp(&b.x)));
+            for (brick_entity, _) in destroyed_bricks {
                 // Hit the brick
                 brick_collision_events.send(BrickCollisionEvent { brick_entity });
+                hit_bricks.push(brick_entity);
+            }
 
-                match collision {
-                    Collision::Left | Collision::Right => {
-                        new_velocity.x = -ball_velocity.x;
-                    }
-                    Collision::Top | Collision::Bottom => {
-                        new_velocity.y = -ball_velocity.y;
-                    }
-                    _ => {}
-                }
+            // Reflect exactly once, only on the axes the ball is moving into
+            if normal.x != 0. && ball_velocity.x * normal.x < 0. {
+                ball_velocity.x = -ball_velocity.x;
+            }
+            if normal.y != 0. && ball_velocity.y * normal.y < 0. {
+                ball_velocity.y = -ball_velocity.y;
+            }
+
+            // The paddle sends the ball off last, whatever else it ran into at the same time
+            if let Some((paddle_entity, paddle_position, paddle_size, paddle_velocity)) =
+                paddle_contact
+            {
+                let half_ball_size = ball_half_size.x;
+                let paddle_half_width = paddle_size.x / 2.;
+                let paddle_top = paddle_position.y + paddle_size.y / 2.;
+
+                // the distance from the center of the paddle, normalized to [-1, 1]
+                let distance_from_center =
+                    (ball_position.x - paddle_position.x) / (paddle_half_width + half_ball_size);
+                // the horizontal motion of the paddle, normalized to [-1, 1]
+                let paddle_motion = paddle_velocity.x / config.paddle_speed;
+                // send the ball off at the angle picked by the configured bounce model
+                ball_velocity.0 = config.paddle_bounce.exit_velocity(
+                    ball_velocity.0.length(),
+                    distance_from_center,
+                    paddle_motion,
+                    config.angle_multiplier,
+                );
+
+                // translate the ball back so it's not inside the paddle
+                ball_position.y = paddle_top + half_ball_size + 0.1;
+                paddle_collision_events.send(PaddleCollisionEvent {
+                    ball_entity,
+                    paddle_entity,
+                });
             }
         }
-        ball_velocity.0 = new_velocity;
+
+        position.0 = ball_position;
     }
 }
 
@@ -381,27 +1103,42 @@ impl Default for Lives {
     }
 }
 
+/// Take balls that left the court out of play, and lose a life when the last one is gone
 pub(crate) fn lives(
     mut commands: Commands,
     mut lives: ResMut<Lives>,
     mut bottom_collision_events: EventReader<BottomCollisionEvent>,
-    mut ball_query: Query<(&mut Transform, &mut Velocity), With<Ball>>,
+    mut life_lost_events: EventWriter<LifeLostEvent>,
+    ball_query: Query<Entity, With<Ball>>,
+    players: Res<Players>,
     mut next_state: ResMut<NextState<BreakoutState>>,
 ) {
-    for _ in bottom_collision_events.iter() {
-        lives.0 = lives.0.saturating_sub(1);
-
-        if lives.0 == 0 {
-            commands.insert_resource(GameResult::GameOver);
-            next_state.set(BreakoutState::Finished);
-        } else {
-            for (mut ball_transform, mut ball_velocity) in &mut ball_query.iter_mut() {
-                ball_transform.translation = Vec3::new(0., 0., 0.);
-                ball_velocity.0 = Vec2::new(0., 0.);
-            }
-            next_state.set(BreakoutState::Serve);
+    let mut lost_balls = Vec::new();
+    for BottomCollisionEvent { ball_entity } in bottom_collision_events.iter() {
+        if !lost_balls.contains(ball_entity) {
+            commands.entity(*ball_entity).despawn_recursive();
+            lost_balls.push(*ball_entity);
         }
     }
+
+    let balls_left = ball_query
+        .iter()
+        .filter(|ball_entity| !lost_balls.contains(ball_entity))
+        .count();
+    if lost_balls.is_empty() || balls_left > 0 {
+        return;
+    }
+
+    lives.0 = lives.0.saturating_sub(1);
+    life_lost_events.send(LifeLostEvent);
+
+    // The game goes on for as long as anyone has lives left
+    if lives.0 == 0 && !players.others_have_lives() {
+        commands.insert_resource(GameResult::GameOver);
+        next_state.set(BreakoutState::Finished);
+    } else {
+        next_state.set(BreakoutState::Serve);
+    }
 }
 
 #[derive(Component)]
@@ -413,6 +1150,7 @@ pub(crate) fn show_game_finished(
     game_result: Res<GameResult>,
     score: Res<Score>,
     lives: Res<Lives>,
+    players: Res<Players>,
 ) {
     commands
         .spawn((
@@ -436,8 +1174,9 @@ pub(crate) fn show_game_finished(
                 Name::new("Game result text"),
                 TextBundle::from_section(
                     match *game_result {
-                        GameResult::Victory => "Victory",
-                        GameResult::GameOver => "Game over",
+                        GameResult::Victory => "Victory".to_string(),
+                        GameResult::GameOver => "Game over".to_string(),
+                        GameResult::PlayerWon(winner) => format!("Player {} wins", winner + 1),
                     },
                     TextStyle {
                         font: asset_server.load(FONT_PATH),
@@ -451,57 +1190,63 @@ pub(crate) fn show_game_finished(
                 }),
             ));
 
-            parent.spawn((
-                Name::new("Final score"),
-                TextBundle::from_section(
-                    "Final score:",
-                    TextStyle {
-                        font: asset_server.load(FONT_PATH),
-                        font_size: 20.,
-                        color: Color::WHITE,
-                    },
-                )
-                .with_style(Style {
-                    margin: UiRect::top(Val::Px(15.)),
-                    ..default()
-                }),
-            ));
+            if players.is_multiplayer() {
+                // The winner of versus is already named, whatever the scores say
+                let name_winner = !matches!(*game_result, GameResult::PlayerWon(_));
+                show_player_comparison(parent, &asset_server, &players, name_winner);
+            } else {
+                parent.spawn((
+                    Name::new("Final score"),
+                    TextBundle::from_section(
+                        "Final score:",
+                        TextStyle {
+                            font: asset_server.load(FONT_PATH),
+                            font_size: 20.,
+                            color: Color::WHITE,
+                        },
+                    )
+                    .with_style(Style {
+                        margin: UiRect::top(Val::Px(15.)),
+                        ..default()
+                    }),
+                ));
 
-            let score_text_style = TextStyle {
-                font: asset_server.load(FONT_PATH),
-                font_size: 20.,
-                color: Color::WHITE,
-            };
-            parent.spawn((
-                Name::new("Score"),
-                TextBundle::from_sections([
-                    TextSection::new(score.0.to_string(), score_text_style.clone()),
-                    TextSection::new(" + ", score_text_style.clone()),
-                    TextSection::new((lives.0 * 100).to_string(), score_text_style.clone()),
-                    TextSection::new(" x 100", score_text_style.clone()),
-                ])
-                .with_style(Style {
-                    margin: UiRect::vertical(Val::Px(5.)),
-                    ..default()
-                }),
-            ));
+                let score_text_style = TextStyle {
+                    font: asset_server.load(FONT_PATH),
+                    font_size: 20.,
+                    color: Color::WHITE,
+                };
+                parent.spawn((
+                    Name::new("Score"),
+                    TextBundle::from_sections([
+                        TextSection::new(score.0.to_string(), score_text_style.clone()),
+                        TextSection::new(" + ", score_text_style.clone()),
+                        TextSection::new((lives.0 * 100).to_string(), score_text_style.clone()),
+                        TextSection::new(" x 100", score_text_style.clone()),
+                    ])
+                    .with_style(Style {
+                        margin: UiRect::vertical(Val::Px(5.)),
+                        ..default()
+                    }),
+                ));
 
-            parent.spawn((
-                Name::new("Final score"),
-                TextBundle::from_sections([
-                    TextSection::new("= ", score_text_style.clone()),
-                    TextSection::new((lives.0 * 100 + score.0).to_string(), score_text_style),
-                ])
-                .with_style(Style {
-                    margin: UiRect::bottom(Val::Px(15.)),
-                    ..default()
-                }),
-            ));
+                parent.spawn((
+                    Name::new("Final score"),
+                    TextBundle::from_sections([
+                        TextSection::new("= ", score_text_style.clone()),
+                        TextSection::new((lives.0 * 100 + score.0).to_string(), score_text_style),
+                    ])
+                    .with_style(Style {
+                        margin: UiRect::bottom(Val::Px(15.)),
+                        ..default()
+                    }),
+                ));
+            }
 
             parent.spawn((
                 Name::new("Restart prompt"),
                 TextBundle::from_section(
-                    "click to restart",
+                    "click, tap or press serve to restart",
                     TextStyle {
                         font: asset_server.load(FONT_PATH),
                         font_size: 10.,
@@ -516,6 +1261,72 @@ pub(crate) fn show_game_finished(
         });
 }
 
+/// Line up the final scores of every player, and name the one with the best score
+fn show_player_comparison(
+    parent: &mut ChildBuilder,
+    asset_server: &AssetServer,
+    players: &Players,
+    name_winner: bool,
+) {
+    let style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: 15.,
+        color: Color::WHITE,
+    };
+
+    for (index, player) in players.players.iter().enumerate() {
+        parent.spawn((
+            Name::new("Player score"),
+            TextBundle::from_section(
+                format!(
+                    "player {}: {} + {} x 100 = {}",
+                    index + 1,
+                    player.score,
+                    player.lives,
+                    player.final_score()
+                ),
+                style.clone(),
+            )
+            .with_style(Style {
+                margin: UiRect::vertical(Val::Px(5.)),
+                ..default()
+            }),
+        ));
+    }
+    if !name_winner {
+        return;
+    }
+
+    let best_score = players
+        .players
+        .iter()
+        .map(|player| player.final_score())
+        .max()
+        .unwrap_or_default();
+    let winners: Vec<usize> = (0..players.players.len())
+        .filter(|index| players.players[*index].final_score() == best_score)
+        .collect();
+    let result = match winners[..] {
+        [winner] => format!("player {} wins", winner + 1),
+        _ => "draw".to_string(),
+    };
+    parent.spawn((
+        Name::new("Winner"),
+        TextBundle::from_section(
+            result,
+            TextStyle {
+                font_size: 20.,
+                color: HIGHLIGHT_COLOR,
+                ..style
+            },
+        )
+        .with_style(Style {
+            margin: UiRect::vertical(Val::Px(15.)),
+            ..default()
+        }),
+    ));
+}
+
 pub(crate) fn reset_lives(mut lives: ResMut<Lives>) {
     *lives = default();
 }
@@ -527,22 +1338,130 @@ pub(crate) fn reset_score(mut score: ResMut<Score>) {
     *score = default();
 }
 
+/// Sent when a brick is destroyed
+pub(crate) struct BrickDestroyedEvent {
+    /// The brick, which is despawned along with the event
+    pub(crate) brick_entity: Entity,
+    /// The court the brick was in
+    pub(crate) court: Entity,
+    pub(crate) kind: BrickKind,
+    pub(crate) points: u32,
+    /// Top row of the brick on the level grid
+    pub(crate) row: usize,
+    pub(crate) position: Vec2,
+    pub(crate) size: Vec2,
+}
+
 pub(crate) fn brick_collision(
     mut commands: Commands,
-    mut score: ResMut<Score>,
+    mut court_players: CourtPlayers,
     mut brick_collision_events: EventReader<BrickCollisionEvent>,
-    brick_query: Query<&Brick>,
+    mut brick_destroyed_events: EventWriter<BrickDestroyedEvent>,
+    court_query: Query<&Court>,
+    mut brick_query: Query<(&mut Brick, &Transform, &Collider, &Parent)>,
 ) {
+    let mut destroyed_bricks = Vec::new();
+
     for BrickCollisionEvent { brick_entity } in brick_collision_events.iter() {
-        let brick_points = brick_query.get(*brick_entity).unwrap().points;
-        score.0 += brick_points;
+        // The ball and an explosion can both hit a brick that is already on its way out
+        if destroyed_bricks.contains(brick_entity) {
+            continue;
+        }
+        let Ok((mut brick, brick_transform, brick_collider, parent)) =
+            brick_query.get_mut(*brick_entity)
+        else {
+            continue;
+        };
+        if brick.kind == BrickKind::Unbreakable {
+            continue;
+        }
+        let Ok(court) = court_query.get(parent.get()) else {
+            continue;
+        };
+
+        brick.hits_left = brick.hits_left.saturating_sub(1);
+        let destroyed = brick.hits_left == 0;
+        *court_players.score_mut(court.index) += brick.kind.score(brick.points, destroyed);
 
-        commands.entity(*brick_entity).despawn_recursive();
+        if destroyed {
+            commands.entity(*brick_entity).despawn_recursive();
+            destroyed_bricks.push(*brick_entity);
+            brick_destroyed_events.send(BrickDestroyedEvent {
+                brick_entity: *brick_entity,
+                court: parent.get(),
+                kind: brick.kind,
+                points: brick.points,
+                row: brick.row,
+                position: brick_transform.translation.truncate(),
+                size: brick_collider.get_size(),
+            });
+        }
+    }
+}
+
+/// Hit every brick touching an explosive brick that was destroyed
+///
+/// The hits are resolved on the next tick, so explosions chain through neighboring explosive
+/// bricks.
+pub(crate) fn explode_bricks(
+    mut brick_destroyed_events: EventReader<BrickDestroyedEvent>,
+    mut brick_collision_events: EventWriter<BrickCollisionEvent>,
+    config: Res<BreakoutConfig>,
+    brick_query: Query<(Entity, &Brick, &Transform, &Collider, &Parent)>,
+) {
+    for destroyed in brick_destroyed_events.iter() {
+        if destroyed.kind != BrickKind::Explosive {
+            continue;
+        }
+
+        // Grow the exploding brick by the padding, so it reaches across the gaps between bricks
+        let blast_half_size = destroyed.size / 2. + config.brick_padding;
+        let mut hit_bricks: Vec<(Entity, Vec2)> = brick_query
+            .iter()
+            .filter(|(_, brick, .., parent)| {
+                brick.kind != BrickKind::Unbreakable && parent.get() == destroyed.court
+            })
+            .filter_map(|(brick_entity, _, brick_transform, brick_collider, _)| {
+                let brick_position = brick_transform.translation.truncate();
+                penetration_aabb(
+                    brick_position,
+                    brick_collider.get_half_size(),
+                    destroyed.position,
+                    blast_half_size,
+                )
+                .map(|_| (brick_entity, brick_position))
+            })
+            .collect();
+
+        // Keep the order of the hits independent of query iteration order
+        hit_bricks.sort_by(|(_, a), (_, b)| b.y.total_cmp(&a.y).then(a.x.total_cmp(&b.x)));
+        for (brick_entity, _) in hit_bricks {
+            brick_collision_events.send(BrickCollisionEvent { brick_entity });
+        }
+    }
+}
+
+/// Fade bricks towards black as they take damage
+pub(crate) fn show_brick_damage(
+    mut brick_query: Query<(&Brick, &BrickColor, &mut Sprite), Changed<Brick>>,
+) {
+    for (brick, brick_color, mut sprite) in &mut brick_query {
+        let health = brick.hits_left as f32 / brick.kind.hits() as f32;
+        let brightness = 0.4 + 0.6 * health;
+        let color = brick_color.0;
+        sprite.color = Color::rgba(
+            color.r() * brightness,
+            color.g() * brightness,
+            color.b() * brightness,
+            color.a(),
+        );
     }
 }
 
 pub(crate) fn bricks_cleared(brick_query: Query<&Brick>) -> bool {
-    brick_query.is_empty()
+    brick_query
+        .iter()
+        .all(|brick| brick.kind == BrickKind::Unbreakable)
 }
 
 pub(crate) fn finish_game(
@@ -553,6 +1472,90 @@ pub(crate) fn finish_game(
     next_state.set(BreakoutState::Finished);
 }
 
+pub(crate) fn clear_level(mut next_state: ResMut<NextState<BreakoutState>>) {
+    next_state.set(BreakoutState::LevelCleared);
+}
+
+/// How long the level cleared message is shown before the next level starts
+const LEVEL_CLEARED_DURATION: f32 = 2.;
+
+#[derive(Resource, Deref, DerefMut)]
+pub(crate) struct LevelClearedTimer(Timer);
+
+pub(crate) fn start_level_cleared_timer(mut commands: Commands) {
+    commands.insert_resource(LevelClearedTimer(Timer::from_seconds(
+        LEVEL_CLEARED_DURATION,
+        TimerMode::Once,
+    )));
+}
+
+/// Move on to serving in the next level once the level cleared message has been shown
+pub(crate) fn start_next_level(
+    fixed_time: Res<FixedTime>,
+    mut timer: ResMut<LevelClearedTimer>,
+    mut next_state: ResMut<NextState<BreakoutState>>,
+) {
+    if timer.tick(fixed_time.period).finished() {
+        next_state.set(BreakoutState::Serve);
+    }
+}
+
+#[derive(Component)]
+struct LevelClearedText;
+
+pub(crate) fn show_level_cleared(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    progress: Res<LevelProgress>,
+) {
+    commands
+        .spawn((
+            LevelClearedText,
+            Name::new("Level cleared text"),
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    position_type: PositionType::Absolute,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Name::new("Level cleared"),
+                TextBundle::from_section(
+                    "Level cleared",
+                    TextStyle {
+                        font: asset_server.load(FONT_PATH),
+                        font_size: 30.,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::vertical(Val::Px(15.)),
+                    ..default()
+                }),
+            ));
+
+            // Progress has already moved on to the next level
+            parent.spawn((
+                Name::new("Next level"),
+                TextBundle::from_section(
+                    format!("get ready for level {}", progress.0 + 1),
+                    TextStyle {
+                        font: asset_server.load(FONT_PATH),
+                        font_size: 10.,
+                        color: Color::WHITE,
+                    },
+                ),
+            ));
+        });
+}
+
 pub(crate) fn restart_game(mut next_state: ResMut<NextState<BreakoutState>>) {
     next_state.set(BreakoutState::Start);
 }
@@ -561,31 +1564,317 @@ pub(crate) fn start_serve(mut next_state: ResMut<NextState<BreakoutState>>) {
     next_state.set(BreakoutState::Serve);
 }
 
+/// Resize the courts, paddles and balls in place when their configured sizes change
+pub(crate) fn update_court_geometry(
+    config: Res<BreakoutConfig>,
+    mut court_query: Query<(&Court, &mut Collider, &mut Sprite, &mut Transform)>,
+    mut court_line_query: Query<
+        (&mut Sprite, &mut Transform),
+        (
+            With<CourtLine>,
+            Without<Court>,
+            Without<Paddle>,
+            Without<Ball>,
+        ),
+    >,
+    mut paddle_query: Query<
+        (&Paddle, &mut Collider, &mut Sprite, &mut Position),
+        (Without<Court>, Without<Ball>),
+    >,
+    mut ball_query: Query<(&mut Collider, &mut Sprite), (With<Ball>, Without<Court>)>,
+    paddle_width_scale: Res<PaddleWidthScale>,
+) {
+    let court_size = Vec2::from_array(config.court_size);
+    let court_count = court_query.iter().len();
+    for (court, mut collider, mut sprite, mut transform) in &mut court_query {
+        *collider = Collider::new(court_size.x, court_size.y);
+        sprite.custom_size = Some(court_size);
+        transform.translation = court_translation(&config, court.index, court_count);
+    }
+
+    for (mut sprite, mut transform) in &mut court_line_query {
+        sprite.custom_size = Some(court_size + Vec2::new(COURT_LINE_WIDTH * 2., COURT_LINE_WIDTH));
+        transform.translation.y = COURT_LINE_WIDTH * 0.5;
+    }
+
+    let paddle_size = paddle_size(&config, &paddle_width_scale);
+    for (paddle, mut collider, mut sprite, mut position) in &mut paddle_query {
+        *collider = Collider::new(paddle_size.x, paddle_size.y);
+        sprite.custom_size = Some(paddle_size);
+        position.y = paddle.home_position(&config).y;
+    }
+
+    for (mut collider, mut sprite) in &mut ball_query {
+        *collider = Collider::new(config.ball_size, config.ball_size);
+        sprite.custom_size = Some(Vec2::splat(config.ball_size));
+    }
+}
+
+fn paddle_size(config: &BreakoutConfig, paddle_width_scale: &PaddleWidthScale) -> Vec2 {
+    Vec2::new(
+        config.paddle_size[0] * paddle_width_scale.0,
+        config.paddle_size[1],
+    )
+}
+
+/// Resize the paddles when their width scale changes, keeping them inside their lanes
+pub(crate) fn resize_paddles(
+    config: Res<BreakoutConfig>,
+    paddle_width_scale: Res<PaddleWidthScale>,
+    mut paddle_query: Query<(&Paddle, &mut Collider, &mut Sprite, &mut Position)>,
+) {
+    let paddle_size = paddle_size(&config, &paddle_width_scale);
+    for (paddle, mut collider, mut sprite, mut position) in &mut paddle_query {
+        *collider = Collider::new(paddle_size.x, paddle_size.y);
+        sprite.custom_size = Some(paddle_size);
+        let (min_x, max_x) = paddle.x_range(&config, paddle_size.x / 2.);
+        position.x = position.x.clamp(min_x, max_x);
+    }
+}
+
+/// Hand the serve to the next paddle after a life is lost
+pub(crate) fn pass_serve(mut serving_paddle: ResMut<ServingPaddle>, paddle_query: Query<&Paddle>) {
+    let paddle_count = paddle_query.iter().count().max(1);
+    serving_paddle.0 = (serving_paddle.0 + 1) % paddle_count;
+}
+
+pub(crate) fn reset_serving_paddle(mut serving_paddle: ResMut<ServingPaddle>) {
+    *serving_paddle = default();
+}
+
+/// Length of a tick at this tick rate, or `None` for rates the simulation can't run at, like
+/// zero or below
+pub(crate) fn tick_period(tick_rate: f32) -> Option<Duration> {
+    Duration::try_from_secs_f32(1. / tick_rate)
+        .ok()
+        .filter(|period| !period.is_zero())
+}
+
+/// Keep the fixed timestep in sync with the configured tick rate, keeping the previous one when
+/// the new one is invalid
+pub(crate) fn update_tick_rate(config: Res<BreakoutConfig>, mut fixed_time: ResMut<FixedTime>) {
+    let Some(period) = tick_period(config.tick_rate) else {
+        warn!("Ignoring the invalid tick rate {}", config.tick_rate);
+        return;
+    };
+    if fixed_time.period != period {
+        fixed_time.period = period;
+    }
+}
+
+pub(crate) fn reset_paddle_width_scale(mut paddle_width_scale: ResMut<PaddleWidthScale>) {
+    *paddle_width_scale = default();
+}
+
+pub(crate) fn reset_ball_speed_scale(mut ball_speed_scale: ResMut<BallSpeedScale>) {
+    *ball_speed_scale = default();
+}
+
 pub(crate) fn clear_game_result(mut commands: Commands) {
     commands.remove_resource::<GameResult>();
 }
 
-pub(crate) struct BreakoutPlugin;
+/// The stages of a single simulation tick, run in order in `CoreSchedule::FixedUpdate`
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub(crate) enum SimulationSet {
+    /// Take the snapshot of the inputs for this tick
+    Input,
+    /// Move the paddles and the ball
+    Movement,
+    /// Apply the rules of the game to what happened during movement
+    Rules,
+    /// Apply the state transitions queued by the rules, so the next tick starts in the new state
+    Transition,
+}
 
-impl Plugin for BreakoutPlugin {
+/// The rules of the game and its physics, with nothing on screen to show for it
+///
+/// Sprites are plain components, so this runs without a window under `MinimalPlugins`, with an
+/// `AssetPlugin` to load the levels.
+pub(crate) struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<BreakoutConfig>()
+        app.init_resource::<BreakoutConfig>();
+
+        let tick_rate = app.world.resource::<BreakoutConfig>().tick_rate;
+        let period = tick_period(tick_rate).unwrap_or_else(|| {
+            warn!("Ignoring the invalid tick rate {tick_rate}");
+            tick_period(BreakoutConfig::default().tick_rate).unwrap()
+        });
+        app.insert_resource(FixedTime::new(period))
             .register_type::<BreakoutConfig>()
+            .register_type::<PaddleBounce>()
+            .register_type::<PowerUpConfig>()
+            .register_type::<DifficultyConfig>()
+            .register_type::<VersusConfig>()
+            .register_type::<Position>()
             .register_type::<Velocity>()
+            .add_plugin(LevelPlugin)
             .init_resource::<Lives>()
             .init_resource::<Score>()
-            .add_event::<BrickCollisionEvent>()
-            .add_event::<BottomCollisionEvent>()
+            .init_resource::<Events<BrickCollisionEvent>>()
+            .init_resource::<Events<BrickDestroyedEvent>>()
+            .init_resource::<Events<BottomCollisionEvent>>()
+            .init_resource::<Events<PaddleCollisionEvent>>()
+            .init_resource::<Events<WallCollisionEvent>>()
+            .init_resource::<Events<LifeLostEvent>>()
+            .init_resource::<Events<SplitBallsEvent>>()
+            .init_resource::<PaddleWidthScale>()
+            .init_resource::<BallSpeedScale>()
+            .init_resource::<ServingPaddle>()
+            .init_resource::<GameRng>()
             .add_state::<BreakoutState>()
+            .add_plugin(PlayersPlugin)
+            .add_plugin(VersusPlugin)
             .add_startup_system(setup_court)
-            .add_system(spawn_bricks.in_schedule(OnEnter(BreakoutState::Start)))
+            .add_system(update_tick_rate.run_if(resource_changed::<BreakoutConfig>()))
+            .add_system(update_court_geometry.run_if(resource_changed::<BreakoutConfig>()))
+            .add_system(reset_paddle_width_scale.in_schedule(OnExit(BreakoutState::Finished)))
+            .add_system(reset_ball_speed_scale.in_schedule(OnExit(BreakoutState::Finished)))
+            .add_system(respawn_changed_bricks)
+            .add_systems(
+                (spawn_bricks, reset_serving_paddle).in_schedule(OnExit(BreakoutState::Start)),
+            )
+            .add_systems(
+                (
+                    advance_level_progress,
+                    despawn_with::<Brick>,
+                    start_level_cleared_timer,
+                )
+                    .chain()
+                    .in_schedule(OnEnter(BreakoutState::LevelCleared)),
+            )
+            .add_system(spawn_bricks.in_schedule(OnExit(BreakoutState::LevelCleared)))
             .add_system(despawn_with::<Ball>.in_schedule(OnExit(BreakoutState::Playing)))
             .add_system(spawn_ball.in_schedule(OnEnter(BreakoutState::Serve)))
-            .add_system(show_game_finished.in_schedule(OnEnter(BreakoutState::Finished)))
             .add_system(despawn_with::<Brick>.in_schedule(OnExit(BreakoutState::Finished)))
-            .add_system(despawn_with::<FinishedText>.in_schedule(OnExit(BreakoutState::Finished)))
             .add_system(reset_lives.in_schedule(OnExit(BreakoutState::Finished)))
             .add_system(reset_score.in_schedule(OnExit(BreakoutState::Finished)))
-            .add_system(clear_game_result.in_schedule(OnExit(BreakoutState::Finished)));
+            .add_system(clear_game_result.in_schedule(OnExit(BreakoutState::Finished)))
+            .add_system(reset_level_progress.in_schedule(OnExit(BreakoutState::Finished)));
+
+        app.insert_resource(PaddleInputs(vec![default()]))
+            .init_resource::<TickInputs>()
+            .init_resource::<PreviousTickInputs>()
+            .init_resource::<ServeReleased>()
+            .add_system(reset_serve_released.in_schedule(OnEnter(BreakoutState::Start)))
+            .edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+                schedule.configure_sets(
+                    (
+                        SimulationSet::Input,
+                        SimulationSet::Movement,
+                        SimulationSet::Rules,
+                        SimulationSet::Transition,
+                    )
+                        .chain(),
+                );
+            })
+            .add_system(
+                snapshot_inputs
+                    .in_set(SimulationSet::Input)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_systems(
+                (
+                    paddle_movement.run_if(not(in_state(BreakoutState::Finished))),
+                    hold_ball_on_paddle.run_if(
+                        in_state(BreakoutState::Serve).or_else(in_state(BreakoutState::Playing)),
+                    ),
+                    serve.run_if(
+                        in_state(BreakoutState::Serve).or_else(in_state(BreakoutState::Playing)),
+                    ),
+                    ball_movement.run_if(in_state(BreakoutState::Playing)),
+                )
+                    .chain()
+                    .in_set(SimulationSet::Movement)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_systems(
+                (
+                    lives
+                        .run_if(not(versus))
+                        .run_if(on_event::<BottomCollisionEvent>()),
+                    pass_serve.run_if(on_event::<LifeLostEvent>()),
+                    split_balls
+                        .run_if(in_state(BreakoutState::Playing))
+                        .run_if(on_event::<SplitBallsEvent>()),
+                    brick_collision.run_if(on_event::<BrickCollisionEvent>()),
+                    apply_system_buffers,
+                    explode_bricks.run_if(on_event::<BrickDestroyedEvent>()),
+                    // Versus has rules of its own for how the game ends
+                    clear_level
+                        .run_if(in_state(BreakoutState::Playing))
+                        .run_if(not(versus))
+                        .run_if(bricks_cleared)
+                        .run_if(not(final_level)),
+                    finish_game
+                        .run_if(in_state(BreakoutState::Playing))
+                        .run_if(not(versus))
+                        .run_if(bricks_cleared)
+                        .run_if(final_level),
+                    select_campaign_level.run_if(
+                        in_state(BreakoutState::Start)
+                            .or_else(in_state(BreakoutState::LevelCleared)),
+                    ),
+                    track_serve_release.run_if(in_state(BreakoutState::Start)),
+                    // The serve that starts the game also picks the game mode, once the serve
+                    // input that restarted the game has been released
+                    start_serve
+                        .run_if(in_state(BreakoutState::Start))
+                        .run_if(serve_released)
+                        .run_if(serve_just_pressed)
+                        .run_if(level_loaded),
+                    start_next_level
+                        .run_if(in_state(BreakoutState::LevelCleared))
+                        .run_if(level_loaded),
+                    resize_paddles.run_if(resource_changed::<PaddleWidthScale>()),
+                )
+                    .chain()
+                    .in_set(SimulationSet::Rules)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_systems(
+                (
+                    apply_system_buffers,
+                    apply_state_transition::<BreakoutState>,
+                    // Simulation events live for two ticks rather than two frames, so none are
+                    // dropped when a frame runs no ticks
+                    Events::<BrickCollisionEvent>::update_system,
+                    Events::<BrickDestroyedEvent>::update_system,
+                    Events::<BottomCollisionEvent>::update_system,
+                    Events::<PaddleCollisionEvent>::update_system,
+                    Events::<WallCollisionEvent>::update_system,
+                    Events::<LifeLostEvent>::update_system,
+                    Events::<SplitBallsEvent>::update_system,
+                )
+                    .chain()
+                    .in_set(SimulationSet::Transition)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+    }
+}
+
+/// The game, with the messages that show how it is going
+pub(crate) struct BreakoutPlugin;
+
+impl Plugin for BreakoutPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(SimulationPlugin)
+            .add_system(show_brick_damage)
+            .add_system(highlight_game_mode)
+            .add_system(show_game_modes.in_schedule(OnEnter(BreakoutState::Start)))
+            .add_system(despawn_with::<GameModeText>.in_schedule(OnExit(BreakoutState::Start)))
+            .add_system(
+                // Progress has to move on to the next level first
+                show_level_cleared
+                    .after(advance_level_progress)
+                    .in_schedule(OnEnter(BreakoutState::LevelCleared)),
+            )
+            .add_system(
+                despawn_with::<LevelClearedText>.in_schedule(OnExit(BreakoutState::LevelCleared)),
+            )
+            .add_system(show_game_finished.in_schedule(OnEnter(BreakoutState::Finished)))
+            .add_system(despawn_with::<FinishedText>.in_schedule(OnExit(BreakoutState::Finished)));
     }
 }
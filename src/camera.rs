@@ -1,10 +1,12 @@
 use bevy::{prelude::*, window::PrimaryWindow};
 
-use crate::breakout::BreakoutConfig;
+use crate::breakout::{courts_size, BreakoutConfig, Court};
 
-fn calculate_court_scale(window: &Window, config: &BreakoutConfig) -> f32 {
-    let height_ratio = window.height() / config.court_size[1];
-    let width_ratio = window.width() / config.court_size[0];
+/// Scale that fits every court on the screen
+fn calculate_court_scale(window: &Window, config: &BreakoutConfig, court_count: usize) -> f32 {
+    let courts_size = courts_size(config, court_count);
+    let height_ratio = window.height() / courts_size.y;
+    let width_ratio = window.width() / courts_size.x;
     (1. / height_ratio.min(width_ratio)) * (1. / config.scale)
 }
 
@@ -17,7 +19,7 @@ pub(crate) fn setup_camera(
 
     commands.spawn(Camera2dBundle {
         projection: OrthographicProjection {
-            scale: calculate_court_scale(window, &config),
+            scale: calculate_court_scale(window, &config, 1),
             ..default()
         },
         ..default()
@@ -29,10 +31,11 @@ pub(crate) fn adjust_camera_scale(
     mut ui_scale: ResMut<UiScale>,
     config: Res<BreakoutConfig>,
     primary_window: Query<&Window, With<PrimaryWindow>>,
+    court_query: Query<(), With<Court>>,
 ) {
     let window = primary_window.single();
 
-    let current_scale = calculate_court_scale(window, &config);
+    let current_scale = calculate_court_scale(window, &config, court_query.iter().len());
     for mut projection in &mut query {
         projection.scale = current_scale;
     }
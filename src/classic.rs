@@ -0,0 +1,145 @@
+use bevy::prelude::*;
+
+use crate::{
+    breakout::{
+        brick_collision, Ball, BreakoutConfig, BreakoutState, Brick, BrickCollisionEvent,
+        PaddleCollisionEvent, PaddleWidthScale, SimulationSet, Velocity, WallCollisionEvent,
+    },
+    level::CampaignPath,
+};
+
+/// Campaign of the classic rules, two walls of the classic level
+const CLASSIC_CAMPAIGN_PATH: &str = "levels/arcade.campaign.ron";
+
+/// Points for a brick in each row of the wall, from the top
+const ROW_POINTS: [u32; 8] = [7, 7, 7, 7, 4, 4, 1, 1];
+
+/// Rows of the red bricks, from the top
+const RED_ROWS: [usize; 2] = [0, 1];
+
+/// Rows of the orange bricks, from the top
+const ORANGE_ROWS: [usize; 2] = [2, 3];
+
+/// Paddle hits after which the ball speeds up
+const SPEED_UP_HITS: [u32; 2] = [4, 12];
+
+/// How much faster the ball gets with every speed-up
+const SPEED_UP_FACTOR: f32 = 1.25;
+
+/// How much of its width the paddle keeps after the ball hits the top wall
+const SHRUNK_PADDLE_SCALE: f32 = 0.5;
+
+/// What the current ball has done so far, for the speed-ups and the paddle shrinking
+#[derive(Resource, Default)]
+pub(crate) struct ClassicProgress {
+    pub(crate) paddle_hits: u32,
+    pub(crate) hit_orange_row: bool,
+    pub(crate) hit_red_row: bool,
+    pub(crate) hit_top_wall: bool,
+}
+
+impl ClassicProgress {
+    /// Number of times the ball has sped up since the serve
+    pub(crate) fn speed_ups(&self) -> u32 {
+        SPEED_UP_HITS
+            .iter()
+            .filter(|hits| self.paddle_hits >= **hits)
+            .count() as u32
+            + self.hit_orange_row as u32
+            + self.hit_red_row as u32
+    }
+}
+
+/// Score bricks by the row they are in, whatever the level says
+pub(crate) fn score_bricks_by_row(mut brick_query: Query<&mut Brick, Added<Brick>>) {
+    for mut brick in &mut brick_query {
+        brick.points = ROW_POINTS.get(brick.row).copied().unwrap_or(1);
+    }
+}
+
+pub(crate) fn track_classic_progress(
+    mut progress: ResMut<ClassicProgress>,
+    mut paddle_collision_events: EventReader<PaddleCollisionEvent>,
+    mut wall_collision_events: EventReader<WallCollisionEvent>,
+    mut brick_collision_events: EventReader<BrickCollisionEvent>,
+    brick_query: Query<&Brick>,
+) {
+    progress.paddle_hits += paddle_collision_events.iter().count() as u32;
+
+    if wall_collision_events
+        .iter()
+        .any(|event| event.normal.y < 0.)
+    {
+        progress.hit_top_wall = true;
+    }
+
+    for BrickCollisionEvent { brick_entity } in brick_collision_events.iter() {
+        let Ok(brick) = brick_query.get(*brick_entity) else {
+            continue;
+        };
+        if ORANGE_ROWS.contains(&brick.row) {
+            progress.hit_orange_row = true;
+        }
+        if RED_ROWS.contains(&brick.row) {
+            progress.hit_red_row = true;
+        }
+    }
+}
+
+/// Keep the balls at the speed for the speed-ups so far
+pub(crate) fn apply_classic_speed(
+    config: Res<BreakoutConfig>,
+    progress: Res<ClassicProgress>,
+    mut ball_query: Query<&mut Velocity, With<Ball>>,
+) {
+    let speed = config.serve_speed * SPEED_UP_FACTOR.powi(progress.speed_ups() as i32);
+    for mut velocity in &mut ball_query {
+        if velocity.0 != Vec2::ZERO && (velocity.length() - speed).abs() > f32::EPSILON * speed {
+            velocity.0 = velocity.normalize() * speed;
+        }
+    }
+}
+
+/// Halve the paddle once the ball has broken through to the top wall
+pub(crate) fn shrink_paddle(
+    progress: Res<ClassicProgress>,
+    mut paddle_width_scale: ResMut<PaddleWidthScale>,
+) {
+    let scale = if progress.hit_top_wall {
+        SHRUNK_PADDLE_SCALE
+    } else {
+        1.
+    };
+    if paddle_width_scale.0 != scale {
+        paddle_width_scale.0 = scale;
+    }
+}
+
+/// Every new ball starts out slow, with a full paddle
+pub(crate) fn reset_classic_progress(mut progress: ResMut<ClassicProgress>) {
+    *progress = default();
+}
+
+/// The rules of the 1976 arcade game: two walls, speed-ups, a shrinking paddle and 1/4/7 row
+/// scoring
+pub(crate) struct ClassicRulesPlugin;
+
+impl Plugin for ClassicRulesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(CampaignPath(CLASSIC_CAMPAIGN_PATH.to_string()))
+            .init_resource::<ClassicProgress>()
+            .add_system(reset_classic_progress.in_schedule(OnEnter(BreakoutState::Serve)))
+            .add_systems(
+                (
+                    score_bricks_by_row,
+                    track_classic_progress.run_if(in_state(BreakoutState::Playing)),
+                    apply_classic_speed,
+                    shrink_paddle,
+                )
+                    .chain()
+                    .before(brick_collision)
+                    .in_set(SimulationSet::Rules)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+    }
+}
@@ -19,3 +19,116 @@ impl Collider {
         self.get_size() / 2.
     }
 }
+
+/// The earliest contact found while sweeping a box along a movement segment.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SweepHit {
+    /// Fraction of the movement segment at which contact happens, in `[0, 1]`.
+    pub(crate) time: f32,
+    /// Normal of the surface that was hit, pointing towards the moving box.
+    pub(crate) normal: Vec2,
+}
+
+/// Sweep a box of `half_size` moving from `position` by `displacement` against a static box,
+/// returning the time of impact and the normal of the face that was hit.
+///
+/// Boxes that already overlap at the start of the movement are not reported.
+pub(crate) fn sweep_aabb(
+    position: Vec2,
+    half_size: Vec2,
+    displacement: Vec2,
+    target_position: Vec2,
+    target_half_size: Vec2,
+) -> Option<SweepHit> {
+    // Grow the target by the size of the moving box so the sweep becomes a ray cast
+    let expanded_half_size = target_half_size + half_size;
+    let min = target_position - expanded_half_size;
+    let max = target_position + expanded_half_size;
+
+    let mut entry_time = f32::NEG_INFINITY;
+    let mut exit_time = f32::INFINITY;
+    let mut normal = Vec2::ZERO;
+
+    for axis in 0..2 {
+        if displacement[axis] == 0. {
+            // Moving parallel to these faces, so the ray has to start between them
+            if position[axis] <= min[axis] || position[axis] >= max[axis] {
+                return None;
+            }
+            continue;
+        }
+
+        let inverse_displacement = 1. / displacement[axis];
+        let mut near = (min[axis] - position[axis]) * inverse_displacement;
+        let mut far = (max[axis] - position[axis]) * inverse_displacement;
+        if near > far {
+            std::mem::swap(&mut near, &mut far);
+        }
+
+        if near > entry_time {
+            entry_time = near;
+            normal = Vec2::ZERO;
+            normal[axis] = -displacement[axis].signum();
+        }
+        exit_time = exit_time.min(far);
+    }
+
+    if entry_time > exit_time || !(0. ..=1.).contains(&entry_time) {
+        return None;
+    }
+
+    Some(SweepHit {
+        time: entry_time,
+        normal,
+    })
+}
+
+/// Sweep a box of `half_size` moving from `position` by `displacement` against the inside walls
+/// of a container box centered on the origin, returning the earliest wall contact.
+pub(crate) fn sweep_inside(
+    position: Vec2,
+    half_size: Vec2,
+    displacement: Vec2,
+    container_half_size: Vec2,
+) -> Option<SweepHit> {
+    let limit = container_half_size - half_size;
+    let mut earliest: Option<SweepHit> = None;
+
+    for axis in 0..2 {
+        if displacement[axis] == 0. {
+            continue;
+        }
+
+        let direction = displacement[axis].signum();
+        let time = ((limit[axis] * direction - position[axis]) / displacement[axis]).max(0.);
+        if time <= 1. && earliest.map_or(true, |hit| time < hit.time) {
+            let mut normal = Vec2::ZERO;
+            normal[axis] = -direction;
+            earliest = Some(SweepHit { time, normal });
+        }
+    }
+
+    earliest
+}
+
+/// Find how far two overlapping boxes have to be pushed apart, returning the normal of the face
+/// of the static box that is closest to the moving box and the penetration depth along it.
+pub(crate) fn penetration_aabb(
+    position: Vec2,
+    half_size: Vec2,
+    target_position: Vec2,
+    target_half_size: Vec2,
+) -> Option<(Vec2, f32)> {
+    let offset = position - target_position;
+    let overlap = target_half_size + half_size - offset.abs();
+    if overlap.x <= 0. || overlap.y <= 0. {
+        return None;
+    }
+
+    // Push out along the axis with the least overlap
+    if overlap.x < overlap.y {
+        Some((Vec2::new(offset.x.signum(), 0.), overlap.x))
+    } else {
+        Some((Vec2::new(0., offset.y.signum()), overlap.y))
+    }
+}
@@ -0,0 +1,80 @@
+use bevy::{
+    asset::{AssetLoader, Error, LoadContext, LoadedAsset},
+    prelude::*,
+    utils::BoxedFuture,
+};
+
+use crate::breakout::{tick_period, BreakoutConfig};
+
+/// Config file loaded at startup, and reloaded whenever it changes on disk
+pub(crate) const CONFIG_PATH: &str = "config.breakout.ron";
+
+#[derive(Default)]
+pub(crate) struct BreakoutConfigLoader;
+
+impl AssetLoader for BreakoutConfigLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let config: BreakoutConfig = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(config));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["breakout.ron"]
+    }
+}
+
+#[derive(Resource)]
+struct ConfigHandle(Handle<BreakoutConfig>);
+
+fn load_config(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ConfigHandle(asset_server.load(CONFIG_PATH)));
+}
+
+/// Copy the config file into the `BreakoutConfig` resource whenever it is (re)loaded
+fn apply_config(
+    mut config: ResMut<BreakoutConfig>,
+    config_handle: Res<ConfigHandle>,
+    configs: Res<Assets<BreakoutConfig>>,
+    mut config_events: EventReader<AssetEvent<BreakoutConfig>>,
+) {
+    for event in config_events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle }
+                if *handle == config_handle.0 =>
+            {
+                if let Some(loaded_config) = configs.get(handle) {
+                    info!("Applying {CONFIG_PATH}");
+                    let tick_rate = config.tick_rate;
+                    *config = loaded_config.clone();
+                    // A typo while editing the file shouldn't stop the game
+                    if tick_period(config.tick_rate).is_none() {
+                        warn!(
+                            "Ignoring the invalid tick rate {} in {CONFIG_PATH}, keeping {tick_rate}",
+                            config.tick_rate
+                        );
+                        config.tick_rate = tick_rate;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+pub(crate) struct ConfigPlugin;
+
+impl Plugin for ConfigPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<BreakoutConfig>()
+            .init_asset_loader::<BreakoutConfigLoader>()
+            .add_startup_system(load_config)
+            .add_system(apply_config.in_base_set(CoreSet::PreUpdate));
+    }
+}
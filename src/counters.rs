@@ -1,15 +1,39 @@
 use bevy::prelude::*;
 
-use crate::breakout::{BreakoutConfig, Lives, Score, FONT_PATH};
+use crate::{
+    breakout::{courts_size, BreakoutConfig, Court, Lives, Score, FONT_PATH},
+    difficulty::SpeedTier,
+    level::{Campaign, CurrentCampaign, LevelProgress},
+    players::{GameMode, Players, HIGHLIGHT_COLOR},
+    power_up::ActivePowerUps,
+};
 #[derive(Component)]
 pub(crate) struct Counters;
 
+/// A row of counters, as wide as the courts together
+#[derive(Component)]
+pub(crate) struct CounterRow;
+
+#[derive(Component)]
+pub(crate) struct LevelCounter;
+
 #[derive(Component)]
 pub(crate) struct LivesCounter;
 
 #[derive(Component)]
 pub(crate) struct ScoreCounter;
 
+#[derive(Component)]
+pub(crate) struct SpeedCounter;
+
+/// The score of every player, when there is more than one
+#[derive(Component)]
+pub(crate) struct PlayersCounter;
+
+/// Lists the active power-ups with the seconds they have left
+#[derive(Component)]
+pub(crate) struct PowerUpCounter;
+
 pub(crate) fn setup_counters(
     mut commands: Commands,
     config: Res<BreakoutConfig>,
@@ -32,8 +56,9 @@ pub(crate) fn setup_counters(
             NodeBundle {
                 style: Style {
                     size: Size::new(Val::Percent(100.), Val::Auto),
-                    justify_content: JustifyContent::Center,
-                    align_items: AlignItems::FlexStart,
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::FlexStart,
+                    align_items: AlignItems::Center,
                     ..default()
                 },
 
@@ -42,14 +67,17 @@ pub(crate) fn setup_counters(
         ))
         .with_children(|parent| {
             parent
-                .spawn(NodeBundle {
-                    style: Style {
-                        size: Size::new(Val::Px(config.court_size[0]), Val::Auto),
-                        justify_content: JustifyContent::SpaceBetween,
+                .spawn((
+                    CounterRow,
+                    NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(config.court_size[0]), Val::Auto),
+                            justify_content: JustifyContent::SpaceBetween,
+                            ..default()
+                        },
                         ..default()
                     },
-                    ..default()
-                })
+                ))
                 .with_children(|counter_container| {
                     counter_container.spawn((
                         Name::new("Lives counter"),
@@ -67,13 +95,29 @@ pub(crate) fn setup_counters(
                         },
                     ));
 
+                    counter_container.spawn((
+                        Name::new("Level counter"),
+                        LevelCounter,
+                        TextBundle {
+                            text: Text::from_sections([
+                                TextSection::new("level:", style.clone()),
+                                TextSection::new("-", style.clone()),
+                            ]),
+                            style: Style {
+                                margin: UiRect::all(Val::Px(counter_offset)),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                    ));
+
                     counter_container.spawn((
                         Name::new("Score counter"),
                         ScoreCounter,
                         TextBundle {
                             text: Text::from_sections([
                                 TextSection::new("score:", style.clone()),
-                                TextSection::new(score.0.to_string(), style),
+                                TextSection::new(score.0.to_string(), style.clone()),
                             ]),
                             style: Style {
                                 margin: UiRect::all(Val::Px(counter_offset)),
@@ -83,6 +127,52 @@ pub(crate) fn setup_counters(
                         },
                     ));
                 });
+
+            let small_style = TextStyle {
+                font_size: 10.,
+                ..style
+            };
+            parent
+                .spawn((
+                    CounterRow,
+                    NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(config.court_size[0]), Val::Auto),
+                            justify_content: JustifyContent::SpaceBetween,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                ))
+                .with_children(|counter_container| {
+                    counter_container.spawn((
+                        Name::new("Speed counter"),
+                        SpeedCounter,
+                        TextBundle::from_section("", small_style.clone()).with_style(Style {
+                            margin: UiRect::horizontal(Val::Px(counter_offset)),
+                            ..default()
+                        }),
+                    ));
+
+                    counter_container.spawn((
+                        Name::new("Power-up counter"),
+                        PowerUpCounter,
+                        TextBundle::from_section("", small_style.clone()).with_style(Style {
+                            margin: UiRect::horizontal(Val::Px(counter_offset)),
+                            ..default()
+                        }),
+                    ));
+                });
+
+            // The sections for the players are added once the game starts
+            parent.spawn((
+                Name::new("Players counter"),
+                PlayersCounter,
+                TextBundle::from_section("", small_style).with_style(Style {
+                    margin: UiRect::all(Val::Px(counter_offset)),
+                    ..default()
+                }),
+            ));
         });
 }
 
@@ -106,12 +196,135 @@ pub(crate) fn update_score_counter(
     }
 }
 
+pub(crate) fn update_level_counter(
+    progress: Res<LevelProgress>,
+    current_campaign: Res<CurrentCampaign>,
+    campaigns: Res<Assets<Campaign>>,
+    mut level_counter_query: Query<&mut Text, With<LevelCounter>>,
+) {
+    if let Some(campaign) = campaigns.get(&current_campaign.0) {
+        let level = format!("{}/{}", progress.0 + 1, campaign.levels.len());
+        let mut level_counter = level_counter_query.single_mut();
+        if level_counter.sections[1].value != level {
+            level_counter.sections[1].value = level;
+        }
+    }
+}
+
+pub(crate) fn update_speed_counter(
+    speed_tier: Option<Res<SpeedTier>>,
+    mut speed_counter_query: Query<&mut Text, With<SpeedCounter>>,
+) {
+    // Not every game mode has a difficulty curve
+    let Some(speed_tier) = speed_tier else {
+        return;
+    };
+
+    if speed_tier.is_changed() {
+        let mut speed_counter = speed_counter_query.single_mut();
+        speed_counter.sections[0].value = format!("speed:{}", speed_tier.0);
+    }
+}
+
+pub(crate) fn update_power_up_counter(
+    active_power_ups: Option<Res<ActivePowerUps>>,
+    mut power_up_counter_query: Query<&mut Text, With<PowerUpCounter>>,
+) {
+    // Not every game mode has power-ups
+    let Some(active_power_ups) = active_power_ups else {
+        return;
+    };
+
+    let power_ups = active_power_ups
+        .0
+        .iter()
+        .map(|active| {
+            let seconds_left = active.timer.remaining_secs().ceil();
+            format!("{}:{seconds_left}", active.kind.label())
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut power_up_counter = power_up_counter_query.single_mut();
+    if power_up_counter.sections[0].value != power_ups {
+        power_up_counter.sections[0].value = power_ups;
+    }
+}
+
+/// Keep the rows of counters as wide as the courts, however many there are
+pub(crate) fn fit_counters_to_courts(
+    config: Res<BreakoutConfig>,
+    court_query: Query<(), With<Court>>,
+    mut row_query: Query<&mut Style, With<CounterRow>>,
+) {
+    let width = Val::Px(courts_size(&config, court_query.iter().len()).x);
+    for mut style in &mut row_query {
+        if style.size.width != width {
+            style.size.width = width;
+        }
+    }
+}
+
+/// Hide the lives and score of the first player in versus, where the players counter shows
+/// those of both players side by side
+pub(crate) fn show_own_counters(
+    game_mode: Res<GameMode>,
+    mut counter_query: Query<&mut Visibility, Or<(With<LivesCounter>, With<ScoreCounter>)>>,
+) {
+    let visibility = if *game_mode == GameMode::Versus {
+        Visibility::Hidden
+    } else {
+        Visibility::Inherited
+    };
+    for mut counter_visibility in &mut counter_query {
+        *counter_visibility = visibility;
+    }
+}
+
+pub(crate) fn update_players_counter(
+    players: Res<Players>,
+    game_mode: Res<GameMode>,
+    mut players_counter_query: Query<&mut Text, With<PlayersCounter>>,
+) {
+    if !players.is_changed() {
+        return;
+    }
+
+    let mut players_counter = players_counter_query.single_mut();
+    let style = players_counter.sections[0].style.clone();
+    players_counter.sections.truncate(1);
+    if !players.is_multiplayer() {
+        return;
+    }
+
+    for (index, player) in players.players.iter().enumerate() {
+        let color = if game_mode.takes_turns() && index == players.current {
+            HIGHLIGHT_COLOR
+        } else {
+            Color::WHITE
+        };
+        players_counter.sections.push(TextSection::new(
+            format!("  {}up:{} x{}  ", index + 1, player.score, player.lives),
+            TextStyle {
+                color,
+                ..style.clone()
+            },
+        ));
+    }
+}
+
 pub(crate) struct CountersPlugin;
 
 impl Plugin for CountersPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system(setup_counters)
             .add_system(update_lives_counter)
-            .add_system(update_score_counter);
+            .add_system(update_score_counter)
+            .add_system(update_level_counter)
+            .add_system(update_speed_counter)
+            .add_system(update_power_up_counter)
+            .add_system(update_players_counter)
+            .add_system(fit_counters_to_courts)
+            .add_system(show_own_counters.run_if(resource_changed::<GameMode>()));
     }
 }
@@ -0,0 +1,164 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    breakout::{
+        brick_collision, Ball, BreakoutConfig, BreakoutState, Brick, PaddleCollisionEvent,
+        SimulationSet, Velocity,
+    },
+    level::BrickKind,
+};
+
+/// Tunable parameters of the difficulty curve, with speeds in world units per second
+///
+/// The curve starts out from the serve speed of the [`BreakoutConfig`].
+#[derive(Reflect, FromReflect, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub(crate) struct DifficultyConfig {
+    /// The ball never gets faster than this
+    pub(crate) max_ball_speed: f32,
+    /// Speed added every time the ball bounces off a paddle
+    pub(crate) speed_per_paddle_hit: f32,
+    /// Speed added for every second of the rally
+    pub(crate) speed_per_second: f32,
+    /// Speed added once every brick of the level is destroyed, growing with every brick on the
+    /// way there
+    pub(crate) speed_when_cleared: f32,
+    /// Number of tiers the range between the serve speed and the maximum speed is divided into
+    pub(crate) speed_tiers: u32,
+}
+
+impl Default for DifficultyConfig {
+    fn default() -> Self {
+        Self {
+            max_ball_speed: 900.,
+            speed_per_paddle_hit: 8.,
+            speed_per_second: 4.,
+            speed_when_cleared: 150.,
+            speed_tiers: 5,
+        }
+    }
+}
+
+/// What happened since the serve
+#[derive(Resource, Default)]
+pub(crate) struct Rally {
+    pub(crate) paddle_hits: u32,
+    /// Seconds since the serve
+    pub(crate) duration: f32,
+}
+
+/// Number of bricks that have to be destroyed to clear the level, when it started
+#[derive(Resource, Default)]
+pub(crate) struct LevelBricks(pub(crate) usize);
+
+/// How fast the ball currently is, from 1 at the serve speed up to the number of tiers
+#[derive(Resource)]
+pub(crate) struct SpeedTier(pub(crate) u32);
+
+impl Default for SpeedTier {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+/// Remember how many bricks the level started out with whenever new bricks are spawned
+pub(crate) fn count_level_bricks(
+    mut level_bricks: ResMut<LevelBricks>,
+    added_brick_query: Query<(), Added<Brick>>,
+    brick_query: Query<&Brick>,
+) {
+    if !added_brick_query.is_empty() {
+        level_bricks.0 = brick_query
+            .iter()
+            .filter(|brick| brick.kind != BrickKind::Unbreakable)
+            .count();
+    }
+}
+
+pub(crate) fn track_rally(
+    fixed_time: Res<FixedTime>,
+    mut rally: ResMut<Rally>,
+    mut paddle_collision_events: EventReader<PaddleCollisionEvent>,
+) {
+    rally.paddle_hits += paddle_collision_events.iter().count() as u32;
+    rally.duration += fixed_time.period.as_secs_f32();
+}
+
+/// Speed the balls up along the difficulty curve
+pub(crate) fn apply_difficulty(
+    config: Res<BreakoutConfig>,
+    rally: Res<Rally>,
+    level_bricks: Res<LevelBricks>,
+    mut speed_tier: ResMut<SpeedTier>,
+    brick_query: Query<&Brick>,
+    mut ball_query: Query<&mut Velocity, With<Ball>>,
+) {
+    let difficulty = &config.difficulty;
+    let min_speed = config.serve_speed;
+
+    let bricks_left = brick_query
+        .iter()
+        .filter(|brick| brick.kind != BrickKind::Unbreakable)
+        .count();
+    let cleared = if level_bricks.0 > 0 {
+        1. - bricks_left.min(level_bricks.0) as f32 / level_bricks.0 as f32
+    } else {
+        0.
+    };
+
+    let speed = (min_speed
+        + rally.paddle_hits as f32 * difficulty.speed_per_paddle_hit
+        + rally.duration * difficulty.speed_per_second
+        + cleared * difficulty.speed_when_cleared)
+        .clamp(min_speed, difficulty.max_ball_speed.max(min_speed));
+
+    for mut velocity in &mut ball_query {
+        // Balls that are held still stay that way
+        if velocity.0 != Vec2::ZERO && (velocity.length() - speed).abs() > f32::EPSILON * speed {
+            velocity.0 = velocity.normalize() * speed;
+        }
+    }
+
+    let speed_range = difficulty.max_ball_speed - min_speed;
+    let tiers = difficulty.speed_tiers.max(1);
+    let tier = if speed_range > 0. {
+        let progress = (speed - min_speed) / speed_range;
+        ((progress * tiers as f32).floor() as u32 + 1).min(tiers)
+    } else {
+        1
+    };
+    if speed_tier.0 != tier {
+        speed_tier.0 = tier;
+    }
+}
+
+pub(crate) fn reset_rally(mut rally: ResMut<Rally>) {
+    *rally = default();
+}
+
+pub(crate) fn reset_speed_tier(mut speed_tier: ResMut<SpeedTier>) {
+    *speed_tier = default();
+}
+
+pub(crate) struct DifficultyPlugin;
+
+impl Plugin for DifficultyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Rally>()
+            .init_resource::<LevelBricks>()
+            .init_resource::<SpeedTier>()
+            .add_systems((reset_rally, reset_speed_tier).in_schedule(OnEnter(BreakoutState::Serve)))
+            .add_systems(
+                (
+                    count_level_bricks,
+                    track_rally.run_if(in_state(BreakoutState::Playing)),
+                    apply_difficulty.run_if(in_state(BreakoutState::Playing)),
+                )
+                    .chain()
+                    .before(brick_collision)
+                    .in_set(SimulationSet::Rules)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+    }
+}
@@ -0,0 +1,289 @@
+use bevy::prelude::*;
+
+use crate::{
+    breakout::{BreakoutConfig, BreakoutState, PaddleInput},
+    headless::{BallState, BrickState, Simulation},
+    level::{Campaign, CurrentCampaign, CurrentLevel, Level},
+    players::GameMode,
+};
+
+/// Settings of a learning environment, fixed for every episode it plays
+#[derive(Clone, Debug)]
+pub struct EnvironmentConfig {
+    /// Play by the rules of the original arcade game, without power-ups or difficulty curve
+    pub classic: bool,
+    /// Ticks per second to simulate at, or the tick rate of the game
+    pub tick_rate: Option<f32>,
+    /// Ticks every action is repeated for
+    pub ticks_per_step: u32,
+    /// Reward for every life gained, and penalty for every life lost
+    pub life_reward: f32,
+    /// Serve held balls for the agent, so it only has to learn to move the paddle
+    pub auto_serve: bool,
+    /// Steps after which an episode ends, even when the game isn't over
+    pub max_steps: Option<u64>,
+}
+
+impl Default for EnvironmentConfig {
+    fn default() -> Self {
+        Self {
+            classic: false,
+            tick_rate: None,
+            ticks_per_step: 10,
+            life_reward: 10.,
+            auto_serve: true,
+            max_steps: None,
+        }
+    }
+}
+
+/// What the agent sees of the game after a step, in the coordinates of the court
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observation {
+    pub court_size: Vec2,
+    /// Position of the ball that comes down first, or of the lowest ball when none are coming
+    /// down, zero without balls
+    pub ball_position: Vec2,
+    /// Velocity of the same ball in world units per second
+    pub ball_velocity: Vec2,
+    /// Whether that ball is held on the paddle, waiting to be served
+    pub ball_held: bool,
+    pub paddle_position: Vec2,
+    /// The level grid, row by row from the top: zero for an empty cell, the fraction of hits
+    /// left for a breakable brick and minus one for an unbreakable brick
+    pub bricks: Vec<f32>,
+    /// Rows of the brick grid
+    pub rows: usize,
+    /// Columns of the brick grid
+    pub columns: usize,
+}
+
+impl Observation {
+    /// Flatten the observation for a neural network
+    ///
+    /// Laid out as the ball position and velocity, whether the ball is held, the paddle position
+    /// and then the brick grid. Positions are in half court sizes, so within `[-1, 1]`, and
+    /// velocities in court heights per second.
+    pub fn to_vec(&self) -> Vec<f32> {
+        let half_court = self.court_size / 2.;
+        let ball_position = self.ball_position / half_court;
+        let ball_velocity = self.ball_velocity / self.court_size.y;
+        let paddle_position = self.paddle_position / half_court;
+
+        let mut values = vec![
+            ball_position.x,
+            ball_position.y,
+            ball_velocity.x,
+            ball_velocity.y,
+            self.ball_held as u8 as f32,
+            paddle_position.x,
+            paddle_position.y,
+        ];
+        values.extend(&self.bricks);
+        values
+    }
+}
+
+/// A reinforcement learning environment playing solo games of the real simulation
+///
+/// Every step plays an action for a few ticks, and is rewarded for the points scored and the
+/// lives gained or lost along the way. Nothing waits on a clock, so episodes play as fast as
+/// they can be simulated.
+pub struct Environment {
+    config: EnvironmentConfig,
+    simulation: Option<Simulation>,
+    /// Shape of the brick grid, large enough for every level of the campaign
+    rows: usize,
+    columns: usize,
+    steps: u64,
+    /// Whether serve was held on the last tick
+    serving: bool,
+}
+
+impl Environment {
+    pub fn new(config: EnvironmentConfig) -> Self {
+        Self {
+            config,
+            simulation: None,
+            rows: 0,
+            columns: 0,
+            steps: 0,
+            serving: false,
+        }
+    }
+
+    /// Start a new episode, waiting for the first serve
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        let mut simulation = if self.config.classic {
+            Simulation::classic(seed)
+        } else {
+            Simulation::new(seed)
+        };
+        if let Some(tick_rate) = self.config.tick_rate {
+            simulation.set_tick_rate(tick_rate);
+        }
+        simulation.start(GameMode::SinglePlayer);
+
+        (self.rows, self.columns) = campaign_grid_size(simulation.world());
+        self.simulation = Some(simulation);
+        self.steps = 0;
+        self.serving = false;
+        self.observation()
+    }
+
+    /// Play an action, returning what the agent sees next, its reward and whether the episode
+    /// is over
+    pub fn step(&mut self, action: PaddleInput) -> (Observation, f32, bool) {
+        let auto_serve = self.config.auto_serve;
+        let simulation = self
+            .simulation
+            .as_mut()
+            .expect("the environment has to be reset before stepping");
+        let score = simulation.score();
+        let lives = simulation.lives();
+
+        for _ in 0..self.config.ticks_per_step {
+            if simulation.state() == BreakoutState::Finished {
+                break;
+            }
+            let mut input = action.clone();
+            if auto_serve {
+                // Serve has to go down to count, so it is let go of in between
+                input.serve = !self.serving && simulation.balls().iter().any(|ball| ball.held);
+            }
+            self.serving = input.serve;
+            simulation.step(&[input]);
+        }
+        self.steps += 1;
+
+        let reward = simulation.score() as f32 - score as f32
+            + (simulation.lives() as f32 - lives as f32) * self.config.life_reward;
+        let done = simulation.state() == BreakoutState::Finished
+            || self
+                .config
+                .max_steps
+                .map_or(false, |max_steps| self.steps >= max_steps);
+        (self.observation(), reward, done)
+    }
+
+    /// The game being played, to look at more of it than the observations show
+    pub fn simulation(&self) -> Option<&Simulation> {
+        self.simulation.as_ref()
+    }
+
+    fn observation(&self) -> Observation {
+        let simulation = self.simulation.as_ref().unwrap();
+        let paddle_position = simulation
+            .paddles()
+            .first()
+            .map_or(Vec2::ZERO, |paddle| paddle.position);
+        let court_size = simulation.court_size();
+        let balls = simulation.balls();
+        // Seconds until a falling ball reaches the bottom of the court, ignoring the walls
+        let time_to_fall =
+            |ball: &&BallState| (ball.position.y + court_size.y / 2.) / -ball.velocity.y;
+        let ball = balls
+            .iter()
+            .filter(|ball| ball.velocity.y < 0.)
+            .min_by(|a, b| time_to_fall(a).total_cmp(&time_to_fall(b)))
+            .or_else(|| {
+                balls
+                    .iter()
+                    .min_by(|a, b| a.position.y.total_cmp(&b.position.y))
+            });
+
+        Observation {
+            court_size,
+            ball_position: ball.map_or(Vec2::ZERO, |ball| ball.position),
+            ball_velocity: ball.map_or(Vec2::ZERO, |ball| ball.velocity),
+            ball_held: ball.map_or(false, |ball| ball.held),
+            paddle_position,
+            bricks: self.brick_grid(simulation),
+            rows: self.rows,
+            columns: self.columns,
+        }
+    }
+
+    /// Mark every cell of the level grid covered by a brick
+    fn brick_grid(&self, simulation: &Simulation) -> Vec<f32> {
+        let mut grid = vec![0.; self.rows * self.columns];
+        let world = simulation.world();
+        let Some(level) = world
+            .get_resource::<CurrentLevel>()
+            .and_then(|current_level| world.resource::<Assets<Level>>().get(&current_level.0))
+        else {
+            return grid;
+        };
+        let config = world.resource::<BreakoutConfig>();
+        let cell_size = Vec2::new(
+            config.court_size[0] / level.columns as f32,
+            config.brick_height,
+        );
+        // Top left corner of the grid
+        let origin = Vec2::new(
+            -config.court_size[0] / 2.,
+            config.court_size[1] / 2. - config.bricks_top_offset,
+        );
+
+        for brick in simulation.bricks() {
+            let value = brick_value(&brick);
+            let min = brick.position - brick.size / 2.;
+            let max = brick.position + brick.size / 2.;
+            // The cells whose centers the brick covers
+            let columns = cell_range(min.x - origin.x, max.x - origin.x, cell_size.x);
+            let rows = cell_range(origin.y - max.y, origin.y - min.y, cell_size.y);
+            for row in rows.filter(|row| *row < self.rows) {
+                for column in columns.clone().filter(|column| *column < self.columns) {
+                    grid[row * self.columns + column] = value;
+                }
+            }
+        }
+        grid
+    }
+}
+
+fn brick_value(brick: &BrickState) -> f32 {
+    if brick.breakable {
+        brick.hits_left as f32 / brick.hits as f32
+    } else {
+        -1.
+    }
+}
+
+/// Indices of the cells of this size whose centers lie between `start` and `end`
+fn cell_range(start: f32, end: f32, cell_size: f32) -> std::ops::Range<usize> {
+    let first = (start / cell_size - 0.5).ceil().max(0.) as usize;
+    let last = (end / cell_size - 0.5).floor();
+    if last < 0. {
+        return 0..0;
+    }
+    first..last as usize + 1
+}
+
+/// Rows and columns of the largest level of the campaign
+fn campaign_grid_size(world: &World) -> (usize, usize) {
+    let levels = world.resource::<Assets<Level>>();
+    let Some(campaign) = world
+        .get_resource::<CurrentCampaign>()
+        .and_then(|current_campaign| {
+            world
+                .resource::<Assets<Campaign>>()
+                .get(&current_campaign.0)
+        })
+    else {
+        return (0, 0);
+    };
+    campaign
+        .levels
+        .iter()
+        .filter_map(|level| levels.get(level))
+        .fold((0, 0), |(rows, columns), level| {
+            let level_rows = level
+                .bricks
+                .iter()
+                .map(|brick| brick.row + brick.height)
+                .max()
+                .unwrap_or(0);
+            (rows.max(level_rows), columns.max(level.columns))
+        })
+}
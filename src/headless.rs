@@ -0,0 +1,422 @@
+use std::time::{Duration, Instant};
+
+use bevy::{
+    ecs::{event::ManualEventReader, world::EntityRef},
+    prelude::*,
+};
+
+use crate::{
+    add_rules,
+    autoplay::{AutoplayPlugin, Bots, Strategy},
+    breakout::{
+        tick_period, Ball, BreakoutConfig, BreakoutState, Brick, BrickDestroyedEvent, Court,
+        HeldBall, Lives, Paddle, PaddleInput, PaddleInputs, Position, Score, SimulationPlugin,
+        TickInputs, Velocity,
+    },
+    collision::Collider,
+    level::{BrickKind, Campaign, CurrentCampaign, Level, LevelProgress},
+    playback::{seek_to, ReplayInputsPlugin},
+    players::GameMode,
+    replay::{Recording, RecordingPlugin, Replay},
+    rng::GameRng,
+};
+
+/// Longest the levels may take to load before giving up on them
+const LOAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A ball in play, or waiting on a paddle to be served
+#[derive(Clone, Debug, PartialEq)]
+pub struct BallState {
+    /// Identifies the ball for as long as it is in play
+    pub id: u64,
+    /// Index of the court the ball is in, from the left
+    pub court: usize,
+    /// Position relative to the center of the court
+    pub position: Vec2,
+    /// Velocity in world units per second
+    pub velocity: Vec2,
+    /// Whether the ball is held on a paddle until it is served
+    pub held: bool,
+}
+
+/// A paddle, driven by one of the inputs of the game mode
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaddleState {
+    /// Index of the input that drives the paddle
+    pub input: usize,
+    /// Index of the court the paddle is in, from the left
+    pub court: usize,
+    /// Position of the center relative to the center of the court
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+/// A brick standing in a court, or one that was just destroyed
+#[derive(Clone, Debug, PartialEq)]
+pub struct BrickState {
+    /// Identifies the brick, and is never reused for another brick of the same game
+    pub id: u64,
+    /// Index of the court the brick is in, from the left
+    pub court: usize,
+    /// Position of the center relative to the center of the court
+    pub position: Vec2,
+    pub size: Vec2,
+    /// Points the brick is worth
+    pub points: u32,
+    /// Hits it takes to destroy the brick
+    pub hits: u32,
+    /// Hits left before the brick is destroyed, zero once it is
+    pub hits_left: u32,
+    /// Whether the brick can be destroyed at all
+    pub breakable: bool,
+}
+
+/// A game without a window, stepped one tick at a time with scripted inputs
+///
+/// Nothing is drawn and no time passes between ticks, so a game runs as fast as it can be
+/// simulated, and the same seed and inputs always play out the same game.
+pub struct Simulation {
+    app: App,
+    tick: u64,
+    brick_destroyed_reader: ManualEventReader<BrickDestroyedEvent>,
+    destroyed_bricks: Vec<BrickState>,
+}
+
+impl Simulation {
+    /// A game with the power-ups and difficulty curve, waiting for its game mode to be picked
+    pub fn new(seed: u64) -> Self {
+        Self::with_rules(seed, false, None)
+    }
+
+    /// A game by the rules of the original arcade game, waiting for its game mode to be picked
+    pub fn classic(seed: u64) -> Self {
+        Self::with_rules(seed, true, None)
+    }
+
+    /// A recorded game, played back tick by tick in place of the inputs passed to `step` until
+    /// the recording runs out
+    pub fn play_back(replay: &[u8]) -> Result<Self, String> {
+        let replay = Replay::decode(replay)?;
+        Ok(Self::with_rules(replay.seed, replay.classic, Some(replay)))
+    }
+
+    fn with_rules(seed: u64, classic: bool, replay: Option<Replay>) -> Self {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_plugin(TransformPlugin)
+            .add_plugin(HierarchyPlugin)
+            .add_plugin(SimulationPlugin)
+            .add_plugin(AutoplayPlugin);
+        add_rules(&mut app, classic);
+        if let Some(replay) = replay {
+            app.add_plugin(ReplayInputsPlugin { replay });
+        }
+        app.insert_resource(GameRng::seeded(seed));
+        // Ticks only run when they are stepped
+        app.world.resource_mut::<Time>().pause();
+
+        let mut simulation = Self {
+            app,
+            tick: 0,
+            brick_destroyed_reader: default(),
+            destroyed_bricks: Vec::new(),
+        };
+        simulation.load_levels();
+        simulation
+    }
+
+    /// Wait for the campaign and its levels, which load in the background
+    fn load_levels(&mut self) {
+        let started = Instant::now();
+        while !self.levels_loaded() {
+            assert!(
+                started.elapsed() < LOAD_TIMEOUT,
+                "the levels did not load in time"
+            );
+            self.app.update();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn levels_loaded(&self) -> bool {
+        let world = &self.app.world;
+        let (Some(current_campaign), Some(campaigns), Some(levels)) = (
+            world.get_resource::<CurrentCampaign>(),
+            world.get_resource::<Assets<Campaign>>(),
+            world.get_resource::<Assets<Level>>(),
+        ) else {
+            return false;
+        };
+        campaigns
+            .get(&current_campaign.0)
+            .map_or(false, |campaign| campaign.is_loaded(levels))
+    }
+
+    /// Pick a game mode the way a player does, by moving the paddle under it and serving
+    ///
+    /// The game then waits for the serve of the first ball.
+    pub fn start(&mut self, game_mode: GameMode) {
+        assert_eq!(
+            self.state(),
+            BreakoutState::Start,
+            "the game has already started"
+        );
+
+        let court_width = self.app.world.resource::<BreakoutConfig>().court_size[0];
+        let x = game_mode.zone_center(court_width);
+        let mut paddle_query = self
+            .app
+            .world
+            .query_filtered::<&mut Position, With<Paddle>>();
+        for mut position in paddle_query.iter_mut(&mut self.app.world) {
+            position.x = x;
+        }
+
+        // Serve has to go down to count
+        self.step(&[]);
+        self.step(&[PaddleInput {
+            serve: true,
+            ..default()
+        }]);
+    }
+
+    /// Start the game on this level of the campaign, counting from zero, rather than the first
+    pub fn set_level(&mut self, level: usize) {
+        assert_eq!(
+            self.state(),
+            BreakoutState::Start,
+            "the game has already started"
+        );
+        self.app.world.resource_mut::<LevelProgress>().0 = level;
+    }
+
+    /// Move a ball in play, to set up a shot
+    pub fn place_ball(&mut self, id: u64, position: Vec2, velocity: Vec2) {
+        let ball = Entity::from_bits(id);
+        let mut ball = self.app.world.entity_mut(ball);
+        assert!(ball.contains::<Ball>(), "there is no ball {id}");
+        ball.insert((Position(position), Velocity(velocity)));
+    }
+
+    /// Record the game from the serve that starts it, the same way the game records every game
+    /// it plays
+    pub fn record(&mut self) {
+        assert_eq!(
+            self.state(),
+            BreakoutState::Start,
+            "the game has already started"
+        );
+        self.app.add_plugin(RecordingPlugin);
+    }
+
+    /// The game recorded so far, in the format of replay files, to play it back with `play_back`
+    pub fn recording(&self) -> Option<Vec<u8>> {
+        let recording = self.app.world.get_resource::<Recording>()?;
+        Some(
+            recording
+                .encode()
+                .expect("the recording could not be encoded"),
+        )
+    }
+
+    /// Skip to a tick of the game played back, counting from the serve that started it, and
+    /// playing it again from the start when going back
+    pub fn seek(&mut self, tick: u64) {
+        seek_to(&mut self.app.world, tick as usize);
+        self.tick = tick;
+        self.destroyed_bricks.clear();
+        self.brick_destroyed_reader
+            .clear(self.app.world.resource::<Events<BrickDestroyedEvent>>());
+    }
+
+    /// Have a bot pick the inputs of the paddles driven by this input, in place of those passed
+    /// to `step`
+    ///
+    /// Bots play once the game has started.
+    pub fn add_bot(&mut self, input: usize, strategy: impl Strategy + 'static) {
+        self.app
+            .world
+            .resource_mut::<Bots>()
+            .add(input, Box::new(strategy));
+    }
+
+    /// Run a single tick with these inputs, one for every paddle input of the game mode
+    ///
+    /// Missing inputs are left idle.
+    pub fn step(&mut self, inputs: &[PaddleInput]) {
+        let mut paddle_inputs = self.app.world.resource_mut::<PaddleInputs>();
+        for (index, input) in paddle_inputs.iter_mut().enumerate() {
+            *input = inputs.get(index).cloned().unwrap_or_default();
+        }
+        self.app.world.run_schedule(CoreSchedule::FixedUpdate);
+        self.tick += 1;
+
+        let world = &self.app.world;
+        let events = world.resource::<Events<BrickDestroyedEvent>>();
+        self.destroyed_bricks = self
+            .brick_destroyed_reader
+            .iter(events)
+            .map(|destroyed| BrickState {
+                id: destroyed.brick_entity.to_bits(),
+                court: world
+                    .get::<Court>(destroyed.court)
+                    .map_or(0, |court| court.index),
+                position: destroyed.position,
+                size: destroyed.size,
+                points: destroyed.points,
+                hits: destroyed.kind.hits(),
+                hits_left: 0,
+                breakable: destroyed.kind != BrickKind::Unbreakable,
+            })
+            .collect();
+    }
+
+    /// Run a number of ticks, with the inputs of every tick written by a script that gets to see
+    /// the game first
+    pub fn step_n(&mut self, ticks: u64, mut script: impl FnMut(&Simulation) -> Vec<PaddleInput>) {
+        for _ in 0..ticks {
+            let inputs = script(self);
+            self.step(&inputs);
+        }
+    }
+
+    /// Simulate at another tick rate, like the 120 Hz of browsers, trading precision for speed
+    pub fn set_tick_rate(&mut self, tick_rate: f32) {
+        let period = tick_period(tick_rate).expect("the tick rate has to be above zero");
+        self.app.world.resource_mut::<BreakoutConfig>().tick_rate = tick_rate;
+        self.app.world.resource_mut::<FixedTime>().period = period;
+    }
+
+    /// The world the game is simulated in, to look at what the accessors don't show
+    pub(crate) fn world(&self) -> &World {
+        &self.app.world
+    }
+
+    /// Inputs the last tick was played with, including those picked by bots
+    pub fn inputs(&self) -> Vec<PaddleInput> {
+        self.app.world.resource::<TickInputs>().0.clone()
+    }
+
+    /// Number of ticks stepped so far
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Length of a tick in seconds
+    pub fn tick_duration(&self) -> f32 {
+        self.app.world.resource::<FixedTime>().period.as_secs_f32()
+    }
+
+    /// Size of a single court
+    pub fn court_size(&self) -> Vec2 {
+        Vec2::from_array(self.app.world.resource::<BreakoutConfig>().court_size)
+    }
+
+    /// Width and height of a ball
+    pub fn ball_size(&self) -> f32 {
+        self.app.world.resource::<BreakoutConfig>().ball_size
+    }
+
+    pub fn state(&self) -> BreakoutState {
+        self.app.world.resource::<State<BreakoutState>>().0.clone()
+    }
+
+    pub fn game_mode(&self) -> GameMode {
+        *self.app.world.resource::<GameMode>()
+    }
+
+    /// Score of the player whose turn it is, or of the player in the first court
+    pub fn score(&self) -> u32 {
+        self.app.world.resource::<Score>().0
+    }
+
+    /// Lives of the player whose turn it is, or of the player in the first court
+    pub fn lives(&self) -> u32 {
+        self.app.world.resource::<Lives>().0
+    }
+
+    pub fn balls(&self) -> Vec<BallState> {
+        ball_states(&self.app.world)
+    }
+
+    pub fn paddles(&self) -> Vec<PaddleState> {
+        paddle_states(&self.app.world)
+    }
+
+    pub fn bricks(&self) -> Vec<BrickState> {
+        brick_states(&self.app.world)
+    }
+
+    /// Bricks destroyed during the last tick
+    pub fn destroyed_bricks(&self) -> &[BrickState] {
+        &self.destroyed_bricks
+    }
+}
+
+/// Index of the court an entity is in, from the left
+fn court_index(world: &World, entity: EntityRef) -> usize {
+    entity
+        .get::<Parent>()
+        .and_then(|parent| world.get::<Court>(parent.get()))
+        .map_or(0, |court| court.index)
+}
+
+/// Every ball in the world
+pub(crate) fn ball_states(world: &World) -> Vec<BallState> {
+    world
+        .iter_entities()
+        .filter(|entity| entity.contains::<Ball>())
+        .map(|ball| BallState {
+            id: ball.id().to_bits(),
+            court: court_index(world, ball),
+            position: ball
+                .get::<Position>()
+                .map_or(Vec2::ZERO, |position| position.0),
+            velocity: ball
+                .get::<Velocity>()
+                .map_or(Vec2::ZERO, |velocity| velocity.0),
+            held: ball.contains::<HeldBall>(),
+        })
+        .collect()
+}
+
+/// Every paddle in the world
+pub(crate) fn paddle_states(world: &World) -> Vec<PaddleState> {
+    world
+        .iter_entities()
+        .filter_map(|entity| Some((entity, entity.get::<Paddle>()?)))
+        .map(|(entity, paddle)| PaddleState {
+            input: paddle.input,
+            court: court_index(world, entity),
+            position: entity
+                .get::<Position>()
+                .map_or(Vec2::ZERO, |position| position.0),
+            size: entity
+                .get::<Collider>()
+                .map_or(Vec2::ZERO, |collider| collider.get_size()),
+        })
+        .collect()
+}
+
+/// Every brick standing in the world
+pub(crate) fn brick_states(world: &World) -> Vec<BrickState> {
+    world
+        .iter_entities()
+        .filter_map(|entity| Some((entity, entity.get::<Brick>()?)))
+        .map(|(entity, brick)| BrickState {
+            id: entity.id().to_bits(),
+            court: court_index(world, entity),
+            position: entity
+                .get::<Transform>()
+                .map_or(Vec2::ZERO, |transform| transform.translation.truncate()),
+            size: entity
+                .get::<Collider>()
+                .map_or(Vec2::ZERO, |collider| collider.get_size()),
+            points: brick.points,
+            hits: brick.kind.hits(),
+            hits_left: brick.hits_left,
+            breakable: brick.kind != BrickKind::Unbreakable,
+        })
+        .collect()
+}
@@ -0,0 +1,59 @@
+use bevy::{prelude::*, transform::TransformSystem};
+
+use crate::breakout::{Position, SimulationSet};
+
+/// Simulation position at the end of the previous tick
+#[derive(Component, Deref, DerefMut, Default)]
+pub(crate) struct PreviousPosition(pub(crate) Vec2);
+
+fn insert_previous_position(
+    mut commands: Commands,
+    position_query: Query<(Entity, &Position), Without<PreviousPosition>>,
+) {
+    for (entity, position) in &position_query {
+        commands.entity(entity).insert(PreviousPosition(position.0));
+    }
+}
+
+fn store_previous_position(mut position_query: Query<(&Position, &mut PreviousPosition)>) {
+    for (position, mut previous_position) in &mut position_query {
+        previous_position.0 = position.0;
+    }
+}
+
+/// Draw simulated entities between their previous and current position, based on how far the
+/// fixed timestep has progressed towards the next tick
+fn interpolate_transforms(
+    fixed_time: Res<FixedTime>,
+    mut position_query: Query<(&Position, &PreviousPosition, &mut Transform)>,
+) {
+    let overstep =
+        (fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32()).clamp(0., 1.);
+
+    for (position, previous_position, mut transform) in &mut position_query {
+        let interpolated = previous_position.lerp(position.0, overstep);
+        transform.translation = interpolated.extend(transform.translation.z);
+    }
+}
+
+pub(crate) struct InterpolationPlugin;
+
+impl Plugin for InterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            store_previous_position
+                .in_set(SimulationSet::Input)
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        .add_systems(
+            (
+                insert_previous_position,
+                apply_system_buffers,
+                interpolate_transforms,
+            )
+                .chain()
+                .in_base_set(CoreSet::PostUpdate)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, Error, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+/// Campaign played when the game starts, unless a game mode picks another one
+pub(crate) const CAMPAIGN_PATH: &str = "levels/main.campaign.ron";
+
+/// What a brick does when the ball hits it
+#[derive(Deserialize, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BrickKind {
+    /// Destroyed by a single hit, scoring its points when destroyed
+    #[default]
+    Normal,
+    /// Takes `hits` hits to destroy, scoring its points on every hit
+    Tough { hits: u32 },
+    /// Never destroyed and never scores, and doesn't have to be cleared to finish a level
+    Unbreakable,
+    /// Destroyed by a single hit, scoring its points and hitting every brick around it
+    Explosive,
+}
+
+impl BrickKind {
+    /// Number of hits it takes to destroy a brick of this kind
+    pub(crate) fn hits(&self) -> u32 {
+        match self {
+            BrickKind::Tough { hits } => (*hits).max(1),
+            _ => 1,
+        }
+    }
+
+    /// Points scored for a hit on a brick of this kind worth `points`
+    pub(crate) fn score(&self, points: u32, destroyed: bool) -> u32 {
+        match self {
+            BrickKind::Normal | BrickKind::Explosive if destroyed => points,
+            BrickKind::Tough { .. } => points,
+            _ => 0,
+        }
+    }
+}
+
+/// A single brick of a level, placed on the level grid
+#[derive(Clone, Debug)]
+pub(crate) struct LevelBrick {
+    pub(crate) column: usize,
+    pub(crate) row: usize,
+    /// Width of the brick in grid columns
+    pub(crate) width: usize,
+    /// Height of the brick in grid rows
+    pub(crate) height: usize,
+    pub(crate) color: Color,
+    pub(crate) points: u32,
+    pub(crate) kind: BrickKind,
+}
+
+/// A brick layout, loaded from a `.level.ron` file
+#[derive(TypeUuid, Clone, Debug)]
+#[uuid = "765c6dc8-b3c5-47f9-85dd-0c737ce95e1f"]
+pub(crate) struct Level {
+    pub(crate) name: String,
+    /// Number of columns the width of the court is divided into
+    pub(crate) columns: usize,
+    pub(crate) bricks: Vec<LevelBrick>,
+}
+
+/// The look and value of a brick, shared by every brick made from the same template
+#[derive(Deserialize, Clone, Debug)]
+struct BrickTemplate {
+    /// Hex color, like `"#ff0000"`
+    color: String,
+    points: u32,
+    #[serde(default)]
+    kind: BrickKind,
+}
+
+fn one() -> usize {
+    1
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct BrickDefinition {
+    column: usize,
+    row: usize,
+    #[serde(default = "one")]
+    width: usize,
+    #[serde(default = "one")]
+    height: usize,
+    color: String,
+    points: u32,
+    #[serde(default)]
+    kind: BrickKind,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+enum LevelLayout {
+    /// An explicit list of bricks
+    Bricks(Vec<BrickDefinition>),
+    /// An ASCII grid where every character places a single brick made from the template with
+    /// that character in the legend, and `.` or a space leaves the cell empty
+    Grid {
+        rows: Vec<String>,
+        legend: HashMap<char, BrickTemplate>,
+    },
+}
+
+/// The on-disk representation of a `Level`
+#[derive(Deserialize, Clone, Debug)]
+struct LevelFile {
+    name: String,
+    columns: usize,
+    layout: LevelLayout,
+}
+
+fn parse_color(color: &str) -> Result<Color, Error> {
+    Color::hex(color).map_err(|error| Error::msg(format!("invalid brick color {color:?}: {error}")))
+}
+
+impl TryFrom<LevelFile> for Level {
+    type Error = Error;
+
+    fn try_from(level_file: LevelFile) -> Result<Self, Self::Error> {
+        let LevelFile {
+            name,
+            columns,
+            layout,
+        } = level_file;
+
+        if columns == 0 {
+            return Err(Error::msg("a level needs at least one column"));
+        }
+
+        let bricks = match layout {
+            LevelLayout::Bricks(definitions) => definitions
+                .into_iter()
+                .map(|definition| {
+                    if definition.width == 0 || definition.height == 0 {
+                        return Err(Error::msg(format!(
+                            "brick at column {} row {} has no size",
+                            definition.column, definition.row
+                        )));
+                    }
+                    if definition.column + definition.width > columns {
+                        return Err(Error::msg(format!(
+                            "brick at column {} row {} sticks out of the {columns} columns",
+                            definition.column, definition.row
+                        )));
+                    }
+
+                    Ok(LevelBrick {
+                        column: definition.column,
+                        row: definition.row,
+                        width: definition.width,
+                        height: definition.height,
+                        color: parse_color(&definition.color)?,
+                        points: definition.points,
+                        kind: definition.kind,
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            LevelLayout::Grid { rows, legend } => {
+                let mut bricks = Vec::new();
+                for (row, line) in rows.iter().enumerate() {
+                    if line.chars().count() > columns {
+                        return Err(Error::msg(format!(
+                            "row {row} is wider than the {columns} columns"
+                        )));
+                    }
+
+                    for (column, character) in line.chars().enumerate() {
+                        if character == '.' || character == ' ' {
+                            continue;
+                        }
+
+                        let template = legend.get(&character).ok_or_else(|| {
+                            Error::msg(format!("{character:?} in row {row} is not in the legend"))
+                        })?;
+                        bricks.push(LevelBrick {
+                            column,
+                            row,
+                            width: 1,
+                            height: 1,
+                            color: parse_color(&template.color)?,
+                            points: template.points,
+                            kind: template.kind,
+                        });
+                    }
+                }
+                bricks
+            }
+        };
+
+        Ok(Level {
+            name,
+            columns,
+            bricks,
+        })
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let level_file: LevelFile = ron::de::from_bytes(bytes)?;
+            let level = Level::try_from(level_file)?;
+            load_context.set_default_asset(LoadedAsset::new(level));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+/// An ordered list of levels, loaded from a `.campaign.ron` file
+#[derive(TypeUuid, Clone, Debug)]
+#[uuid = "ec283154-fd2e-4464-9626-27d43691243e"]
+pub(crate) struct Campaign {
+    pub(crate) levels: Vec<Handle<Level>>,
+}
+
+impl Campaign {
+    /// Whether every level of the campaign is loaded
+    pub(crate) fn is_loaded(&self, levels: &Assets<Level>) -> bool {
+        self.levels.iter().all(|level| levels.contains(level))
+    }
+}
+
+/// The on-disk representation of a `Campaign`
+#[derive(Deserialize, Clone, Debug)]
+struct CampaignFile {
+    /// Paths of the level files, relative to the asset folder
+    levels: Vec<String>,
+}
+
+#[derive(Default)]
+pub(crate) struct CampaignLoader;
+
+impl AssetLoader for CampaignLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let campaign_file: CampaignFile = ron::de::from_bytes(bytes)?;
+            if campaign_file.levels.is_empty() {
+                return Err(Error::msg("a campaign needs at least one level"));
+            }
+
+            let campaign = Campaign {
+                levels: campaign_file
+                    .levels
+                    .iter()
+                    .map(|path| load_context.get_handle(path.as_str()))
+                    .collect(),
+            };
+            let dependencies = campaign_file
+                .levels
+                .into_iter()
+                .map(AssetPath::from)
+                .collect();
+            load_context
+                .set_default_asset(LoadedAsset::new(campaign).with_dependencies(dependencies));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["campaign.ron"]
+    }
+}
+
+/// Path of the campaign to load, relative to the asset folder
+#[derive(Resource)]
+pub(crate) struct CampaignPath(pub(crate) String);
+
+impl Default for CampaignPath {
+    fn default() -> Self {
+        Self(CAMPAIGN_PATH.to_string())
+    }
+}
+
+/// The campaign that is being played
+#[derive(Resource)]
+pub(crate) struct CurrentCampaign(pub(crate) Handle<Campaign>);
+
+/// Index of the current level within the campaign
+#[derive(Resource, Default)]
+pub(crate) struct LevelProgress(pub(crate) usize);
+
+/// The level that is currently being played
+#[derive(Resource, Default)]
+pub(crate) struct CurrentLevel(pub(crate) Handle<Level>);
+
+fn load_campaign(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    campaign_path: Res<CampaignPath>,
+) {
+    commands.insert_resource(CurrentCampaign(asset_server.load(campaign_path.0.as_str())));
+}
+
+/// Point `CurrentLevel` at the level of the campaign matching the current progress
+pub(crate) fn select_campaign_level(
+    current_campaign: Res<CurrentCampaign>,
+    campaigns: Res<Assets<Campaign>>,
+    progress: Res<LevelProgress>,
+    mut current_level: ResMut<CurrentLevel>,
+) {
+    let Some(campaign) = campaigns.get(&current_campaign.0) else {
+        return;
+    };
+    let Some(level) = campaign.levels.get(progress.0) else {
+        return;
+    };
+    if current_level.0 != *level {
+        current_level.0 = level.clone();
+    }
+}
+
+pub(crate) fn level_loaded(current_level: Res<CurrentLevel>, levels: Res<Assets<Level>>) -> bool {
+    levels.contains(&current_level.0)
+}
+
+/// Whether the campaign and every one of its levels are loaded
+pub(crate) fn campaign_loaded(
+    current_campaign: Res<CurrentCampaign>,
+    campaigns: Res<Assets<Campaign>>,
+    levels: Res<Assets<Level>>,
+) -> bool {
+    campaigns
+        .get(&current_campaign.0)
+        .map_or(false, |campaign| campaign.is_loaded(&levels))
+}
+
+/// Whether the current level is the last one of the campaign
+pub(crate) fn final_level(
+    current_campaign: Res<CurrentCampaign>,
+    campaigns: Res<Assets<Campaign>>,
+    progress: Res<LevelProgress>,
+) -> bool {
+    campaigns
+        .get(&current_campaign.0)
+        .map_or(true, |campaign| progress.0 + 1 >= campaign.levels.len())
+}
+
+pub(crate) fn advance_level_progress(mut progress: ResMut<LevelProgress>) {
+    progress.0 += 1;
+}
+
+pub(crate) fn reset_level_progress(mut progress: ResMut<LevelProgress>) {
+    *progress = default();
+}
+
+pub(crate) struct LevelPlugin;
+
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Level>()
+            .add_asset::<Campaign>()
+            .init_asset_loader::<LevelLoader>()
+            .init_asset_loader::<CampaignLoader>()
+            .init_resource::<CampaignPath>()
+            .init_resource::<LevelProgress>()
+            .init_resource::<CurrentLevel>()
+            .add_startup_system(load_campaign);
+    }
+}
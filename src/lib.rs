@@ -0,0 +1,165 @@
+// Bevy systems routinely take many parameters and queries with long filter tuples
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use actions::ActionsPlugin;
+use autoplay::{strategy_by_name, AutoplayPlugin, Bots};
+use bevy::prelude::*;
+use camera::ScalingCameraPlugin;
+use config::ConfigPlugin;
+
+#[cfg(feature = "inspector")]
+use bevy_inspector_egui::quick::WorldInspectorPlugin;
+
+// The module shares its name with the crate, which older rustdoc confuses it with
+use crate::breakout::BreakoutPlugin;
+use classic::ClassicRulesPlugin;
+use counters::CountersPlugin;
+use difficulty::DifficultyPlugin;
+use interpolation::InterpolationPlugin;
+use local::LocalPlugin;
+use playback::PlaybackPlugin;
+use power_up::PowerUpPlugin;
+use rebinding::RebindingPlugin;
+use replay::{Replay, ReplayPlugin};
+
+pub use crate::breakout::{BreakoutState, PaddleInput};
+pub use autoplay::{AimingBot, BotView, Catch, HumanTracker, PerfectTracker, Strategy};
+pub use environment::{Environment, EnvironmentConfig, Observation};
+pub use headless::{BallState, BrickState, PaddleState, Simulation};
+pub use players::GameMode;
+
+mod actions;
+mod autoplay;
+mod bounce;
+mod breakout;
+mod camera;
+mod classic;
+mod collision;
+mod config;
+mod counters;
+mod difficulty;
+mod environment;
+mod headless;
+mod interpolation;
+mod level;
+mod local;
+mod playback;
+mod players;
+mod power_up;
+mod rebinding;
+mod replay;
+mod rng;
+mod util;
+mod versus;
+
+/// Play the game in a window, or play back a recorded game with `--replay <path>`
+///
+/// `--autoplay <strategy>` has a bot play solo games over and over as a demo, and
+/// `--autoplay-partner <strategy>` has one play the second paddle. The strategies are `perfect`,
+/// `human` and `aiming`.
+pub fn run() {
+    let args: Vec<String> = std::env::args().collect();
+    // `--replay <path>` plays back a recorded game instead of playing one
+    let replay = args
+        .iter()
+        .position(|arg| arg == "--replay")
+        .and_then(|index| args.get(index + 1))
+        .map(|path| {
+            Replay::load(path).unwrap_or_else(|error| {
+                eprintln!("Could not load the replay {path}: {error}");
+                std::process::exit(1);
+            })
+        });
+
+    let mut app = App::new();
+
+    app.add_plugins(
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: Some(Window {
+                    fit_canvas_to_parent: true,
+                    prevent_default_event_handling: false,
+                    ..default()
+                }),
+                ..default()
+            })
+            .set(AssetPlugin {
+                // Reload levels and the config when they are edited
+                watch_for_changes: cfg!(not(target_arch = "wasm32")),
+                ..default()
+            })
+            .set(TaskPoolPlugin {
+                // This seems to resolve stuttering in Bevy 0.10 on Linux
+                task_pool_options: TaskPoolOptions::with_num_threads(1),
+            }),
+    )
+    .insert_resource(ClearColor(Color::BLACK));
+
+    #[cfg(feature = "inspector")]
+    app.add_plugin(WorldInspectorPlugin);
+
+    app.add_plugin(BreakoutPlugin);
+
+    let classic = match &replay {
+        Some(replay) => replay.classic,
+        None => args.iter().any(|arg| arg == "--classic"),
+    };
+    add_rules(&mut app, classic);
+
+    app.add_plugin(ActionsPlugin);
+
+    // A replay brings its own config and inputs
+    match replay {
+        Some(replay) => {
+            app.add_plugin(PlaybackPlugin { replay });
+        }
+        None => {
+            app.add_plugin(ConfigPlugin)
+                .add_plugin(LocalPlugin)
+                .add_plugin(RebindingPlugin)
+                .add_plugin(ReplayPlugin)
+                .add_plugin(AutoplayPlugin);
+            add_bots(&mut app, &args);
+        }
+    }
+
+    app.add_plugin(CountersPlugin)
+        .add_plugin(InterpolationPlugin)
+        .add_plugin(ScalingCameraPlugin);
+
+    app.run();
+}
+
+/// Add the rules on top of the simulation
+///
+/// The classic rules replace the power-ups and difficulty curve with the behavior of the original
+/// arcade game.
+fn add_rules(app: &mut App, classic: bool) {
+    if classic {
+        app.add_plugin(ClassicRulesPlugin);
+    } else {
+        app.add_plugin(PowerUpPlugin).add_plugin(DifficultyPlugin);
+    }
+}
+
+/// Have bots play in place of the players asked for on the command line
+fn add_bots(app: &mut App, args: &[String]) {
+    let mut bots = app.world.resource_mut::<Bots>();
+    for (input, flag) in ["--autoplay", "--autoplay-partner"].into_iter().enumerate() {
+        let Some(name) = args
+            .iter()
+            .position(|arg| arg == flag)
+            .and_then(|index| args.get(index + 1))
+        else {
+            continue;
+        };
+        let strategy = strategy_by_name(name, rand::random()).unwrap_or_else(|error| {
+            eprintln!("Could not start a bot for {flag}: {error}");
+            std::process::exit(1);
+        });
+        bots.add(input, strategy);
+        if input == 0 {
+            bots.demo = Some(GameMode::SinglePlayer);
+        }
+    }
+}
@@ -1,19 +1,108 @@
-use std::time::Duration;
-
 use bevy::{prelude::*, window::PrimaryWindow};
 
 use crate::{
-    breakout::{
-        ball_movement, brick_collision, bricks_cleared, finish_game, lives, paddle_movement,
-        restart_game, serve, start_serve, BottomCollisionEvent, BreakoutState, BrickCollisionEvent,
-        Paddle, PaddleInputs,
-    },
+    actions::{action_just_pressed, Action, ActionState, Binding, Bindings},
+    breakout::{restart_game, BreakoutConfig, BreakoutState, Paddle, PaddleInputs, FONT_PATH},
+    rebinding::RebindingScreen,
     util::cursor_position_in_world,
 };
 
 #[derive(Default, Resource)]
 struct CursorPosition(pub(crate) Vec3);
 
+/// Stick deflection below which a gamepad doesn't count as being used
+const GAMEPAD_STICK_DEAD_ZONE: f32 = 0.2;
+
+/// Longest touch in seconds that still counts as a tap
+const TAP_DURATION: f32 = 0.3;
+
+/// Farthest a touch can move in logical pixels and still count as a tap
+const TAP_DISTANCE: f32 = 10.;
+
+/// How long serve stays pressed after a tap, so the simulation is sure to see it
+const TAP_SERVE_DURATION: f32 = 0.1;
+
+/// The touch that drives the paddle, and the taps it made
+#[derive(Resource, Default)]
+struct TouchState {
+    /// Other touches are ignored for as long as this one lasts, so a second finger doesn't
+    /// make the paddle jump
+    driving_touch: Option<u64>,
+    /// Time the driving touch started
+    start_time: f32,
+    /// Serve is held until this time after a tap
+    serve_until: f32,
+    /// Whether a tap ended this frame
+    tapped: bool,
+}
+
+/// The device the local player used last, the only one driving the paddle
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum InputDevice {
+    #[default]
+    Mouse,
+    Keyboard,
+    Gamepad,
+    Touch,
+}
+
+/// Whether the local player paused the game
+#[derive(Resource, Default)]
+pub(crate) struct Paused(pub(crate) bool);
+
+#[derive(Component)]
+struct PausedText;
+
+fn gamepad_stick_x(gamepads: &Gamepads, axes: &Axis<GamepadAxis>) -> f32 {
+    gamepads
+        .iter()
+        .filter_map(|gamepad| axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX)))
+        .find(|x| x.abs() > GAMEPAD_STICK_DEAD_ZONE)
+        .unwrap_or(0.)
+}
+
+/// Switch to whichever device was just used
+fn detect_input_device(
+    mut input_device: ResMut<InputDevice>,
+    mut cursor_moved_events: EventReader<CursorMoved>,
+    bindings: Res<Bindings>,
+    mouse_button_input: Res<Input<MouseButton>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_button_input: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    touches: Res<Touches>,
+) {
+    let used_device = if touches.iter_just_pressed().next().is_some() {
+        Some(InputDevice::Touch)
+    } else if gamepad_button_input.get_just_pressed().next().is_some()
+        || gamepad_stick_x(&gamepads, &gamepad_axes) != 0.
+    {
+        Some(InputDevice::Gamepad)
+    } else if keyboard_input
+        .get_just_pressed()
+        .any(|key| !bindings.is_bound(&Action::SECOND_PLAYER, Binding::Key(*key)))
+    {
+        Some(InputDevice::Keyboard)
+    } else if cursor_moved_events.iter().count() > 0
+        || mouse_button_input.get_just_pressed().next().is_some()
+    {
+        Some(InputDevice::Mouse)
+    } else {
+        None
+    };
+
+    if let Some(used_device) = used_device {
+        if *input_device != used_device {
+            *input_device = used_device;
+        }
+    }
+}
+
+fn using(device: InputDevice) -> impl Fn(Res<InputDevice>) -> bool {
+    move |input_device: Res<InputDevice>| *input_device == device
+}
+
 fn update_cursor_pos(
     primary_window: Query<&Window, With<PrimaryWindow>>,
     camera_query: Query<(&GlobalTransform, &Camera)>,
@@ -33,45 +122,236 @@ fn update_cursor_pos(
 }
 
 fn mouse_input(
-    mouse_button_input: Res<Input<MouseButton>>,
+    action_state: Res<ActionState>,
     mut inputs: ResMut<PaddleInputs>,
-    paddle_query: Query<&GlobalTransform, With<Paddle>>,
+    paddle_query: Query<(&Paddle, &GlobalTransform)>,
     cursor_position: Res<CursorPosition>,
     projection_query: Query<&OrthographicProjection>,
 ) {
     inputs[0].move_direction = 0.;
-    inputs[0].serve = false;
+    inputs[0].serve = action_state.pressed(Action::Serve);
 
-    if mouse_button_input.pressed(MouseButton::Left) {
-        inputs[0].serve = true;
+    if let Some(paddle_transform) = first_paddle_transform(&paddle_query) {
+        let projection = projection_query.get_single().unwrap();
+        inputs[0].move_direction = move_towards(
+            cursor_position.0.x,
+            paddle_transform.translation().x,
+            projection.scale,
+        );
     }
+}
 
-    if let Ok(paddle_transform) = paddle_query.get_single() {
-        let projection = projection_query.get_single().unwrap();
-        let cursor_paddle_diff =
-            (cursor_position.0.x - paddle_transform.translation().x) * projection.scale;
-        let max_move = cursor_paddle_diff.abs().min(4.);
-        inputs[0].move_direction = (cursor_paddle_diff * 0.1).clamp(-max_move, max_move);
+/// The paddle driven by the devices of the first player
+fn first_paddle_transform<'a>(
+    paddle_query: &'a Query<(&Paddle, &GlobalTransform)>,
+) -> Option<&'a GlobalTransform> {
+    paddle_query
+        .iter()
+        .find(|(paddle, _)| paddle.input == 0)
+        .map(|(_, transform)| transform)
+}
+
+/// Move direction that takes a paddle to a target position in the world
+fn move_towards(target_x: f32, paddle_x: f32, projection_scale: f32) -> f32 {
+    let target_paddle_diff = (target_x - paddle_x) * projection_scale;
+    let max_move = target_paddle_diff.abs().min(4.);
+    (target_paddle_diff * 0.1).clamp(-max_move, max_move)
+}
+
+/// Pick the touch that drives the paddle, and spot taps
+fn track_touches(
+    time: Res<Time>,
+    config: Res<BreakoutConfig>,
+    touches: Res<Touches>,
+    primary_window: Query<&Window, With<PrimaryWindow>>,
+    mut touch_state: ResMut<TouchState>,
+) {
+    let now = time.elapsed_seconds();
+    touch_state.tapped = false;
+
+    if let Some(id) = touch_state.driving_touch {
+        if let Some(touch) = touches.iter_just_released().find(|touch| touch.id() == id) {
+            let tapped = now - touch_state.start_time <= TAP_DURATION
+                && touch.position().distance(touch.start_position()) <= TAP_DISTANCE;
+            if tapped {
+                touch_state.tapped = true;
+                touch_state.serve_until = now + TAP_SERVE_DURATION;
+            }
+            touch_state.driving_touch = None;
+        } else if touches.get_pressed(id).is_none() {
+            // Cancelled
+            touch_state.driving_touch = None;
+        }
     }
+
+    if touch_state.driving_touch.is_none() {
+        let Ok(window) = primary_window.get_single() else {
+            return;
+        };
+        // Touch positions start at the top of the window
+        let dead_zone_top = window.height() * (1. - config.touch_dead_zone);
+        if let Some(touch) = touches
+            .iter_just_pressed()
+            .find(|touch| touch.position().y < dead_zone_top)
+        {
+            touch_state.driving_touch = Some(touch.id());
+            touch_state.start_time = now;
+        }
+    }
+}
+
+fn touch_input(
+    time: Res<Time>,
+    touches: Res<Touches>,
+    touch_state: Res<TouchState>,
+    mut inputs: ResMut<PaddleInputs>,
+    primary_window: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&GlobalTransform, &Camera, &OrthographicProjection)>,
+    paddle_query: Query<(&Paddle, &GlobalTransform)>,
+) {
+    inputs[0].move_direction = 0.;
+    inputs[0].serve = time.elapsed_seconds() < touch_state.serve_until;
+
+    let Some(touch) = touch_state
+        .driving_touch
+        .and_then(|id| touches.get_pressed(id))
+    else {
+        return;
+    };
+    let (Ok(window), Some(paddle_transform)) = (
+        primary_window.get_single(),
+        first_paddle_transform(&paddle_query),
+    ) else {
+        return;
+    };
+
+    for (camera_transform, camera, projection) in &camera_query {
+        // Flip to the bottom-up coordinates the cursor uses
+        let position = Vec2::new(touch.position().x, window.height() - touch.position().y);
+        let touch_position = cursor_position_in_world(window, position, camera_transform, camera);
+        inputs[0].move_direction = move_towards(
+            touch_position.x,
+            paddle_transform.translation().x,
+            projection.scale,
+        );
+    }
+}
+
+/// Move direction of a pair of move actions, all the way one way or the other
+fn action_move_direction(action_state: &ActionState, left: Action, right: Action) -> f32 {
+    action_state.pressed(right) as i32 as f32 - action_state.pressed(left) as i32 as f32
+}
+
+fn keyboard_input(action_state: Res<ActionState>, mut inputs: ResMut<PaddleInputs>) {
+    inputs[0].move_direction =
+        action_move_direction(&action_state, Action::MoveLeft, Action::MoveRight);
+    inputs[0].serve = action_state.pressed(Action::Serve);
 }
 
-fn left_mouse_button_just_pressed(mouse_button_input: Res<Input<MouseButton>>) -> bool {
-    mouse_button_input.just_pressed(MouseButton::Left)
+fn gamepad_input(
+    action_state: Res<ActionState>,
+    gamepads: Res<Gamepads>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    mut inputs: ResMut<PaddleInputs>,
+) {
+    let buttons = action_move_direction(&action_state, Action::MoveLeft, Action::MoveRight);
+
+    inputs[0].move_direction = if buttons != 0. {
+        buttons
+    } else {
+        gamepad_stick_x(&gamepads, &gamepad_axes)
+    };
+    inputs[0].serve = action_state.pressed(Action::Serve);
 }
 
-fn left_mouse_button_pressed(mouse_button_input: Res<Input<MouseButton>>) -> bool {
-    mouse_button_input.pressed(MouseButton::Left)
+/// Drive the paddle of the second player, when there is one
+fn second_player_input(action_state: Res<ActionState>, mut inputs: ResMut<PaddleInputs>) {
+    let Some(input) = inputs.get_mut(1) else {
+        return;
+    };
+    input.move_direction = action_move_direction(
+        &action_state,
+        Action::SecondMoveLeft,
+        Action::SecondMoveRight,
+    );
+    input.serve = action_state.pressed(Action::SecondServe);
 }
 
-fn serve_button_pressed(paddle_inputs: Res<PaddleInputs>) -> bool {
-    paddle_inputs[0].serve
+/// Whether the restart action was just pressed, or the screen tapped
+fn restart_pressed(action_state: Res<ActionState>, touch_state: Res<TouchState>) -> bool {
+    action_state.just_pressed(Action::Restart) || touch_state.tapped
 }
 
-#[derive(SystemSet, Hash, PartialEq, Eq, Clone, Debug)]
-struct BallMovement;
+pub(crate) fn game_paused(paused: Res<Paused>) -> bool {
+    paused.0
+}
 
-fn game_finished(state: Res<State<BreakoutState>>) -> bool {
-    state.0 == BreakoutState::Finished
+fn toggle_pause(mut paused: ResMut<Paused>) {
+    paused.0 = !paused.0;
+}
+
+/// Stop the clock, and with it the simulation, while the game is paused
+fn apply_pause(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    paused: Res<Paused>,
+    mut time: ResMut<Time>,
+    paused_text_query: Query<Entity, With<PausedText>>,
+) {
+    if !paused.0 {
+        time.unpause();
+        for entity in &paused_text_query {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+
+    time.pause();
+    commands
+        .spawn((
+            PausedText,
+            Name::new("Paused text"),
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    position_type: PositionType::Absolute,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Name::new("Paused"),
+                TextBundle::from_section(
+                    "Paused",
+                    TextStyle {
+                        font: asset_server.load(FONT_PATH),
+                        font_size: 30.,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::vertical(Val::Px(15.)),
+                    ..default()
+                }),
+            ));
+
+            parent.spawn((
+                Name::new("Pause prompt"),
+                TextBundle::from_section(
+                    "press pause to resume, F1 to change the controls",
+                    TextStyle {
+                        font: asset_server.load(FONT_PATH),
+                        font_size: 10.,
+                        color: Color::WHITE,
+                    },
+                ),
+            ));
+        });
 }
 
 pub(crate) struct LocalPlugin;
@@ -79,46 +359,49 @@ pub(crate) struct LocalPlugin;
 impl Plugin for LocalPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(CursorPosition(Vec3::ZERO))
-            .insert_resource(PaddleInputs(vec![default()]));
-        app.add_system(update_cursor_pos.before(mouse_input))
-            .add_system(mouse_input)
+            .init_resource::<InputDevice>()
+            .init_resource::<TouchState>()
+            .init_resource::<Paused>()
+            .add_system(update_cursor_pos.before(mouse_input))
+            .add_system(detect_input_device.before(update_cursor_pos))
+            .add_system(mouse_input.run_if(using(InputDevice::Mouse)))
+            .add_system(
+                keyboard_input
+                    .after(detect_input_device)
+                    .run_if(using(InputDevice::Keyboard)),
+            )
             .add_system(
-                paddle_movement
-                    .run_if(not(game_finished))
-                    .after(mouse_input),
+                gamepad_input
+                    .after(detect_input_device)
+                    .run_if(using(InputDevice::Gamepad)),
             )
+            .add_system(second_player_input)
+            .add_system(track_touches)
             .add_system(
-                serve
-                    .in_set(OnUpdate(BreakoutState::Serve))
-                    .run_if(serve_button_pressed)
-                    .after(paddle_movement),
+                touch_input
+                    .after(detect_input_device)
+                    .after(track_touches)
+                    .run_if(using(InputDevice::Touch)),
             )
             .add_system(
                 restart_game
+                    .after(track_touches)
                     .in_set(OnUpdate(BreakoutState::Finished))
-                    .run_if(left_mouse_button_just_pressed),
+                    .run_if(restart_pressed),
             )
             .add_system(
-                start_serve
-                    .in_set(OnUpdate(BreakoutState::Start))
-                    .run_if(not(left_mouse_button_pressed)),
+                toggle_pause
+                    .run_if(action_just_pressed(Action::Pause))
+                    .run_if(
+                        in_state(BreakoutState::Serve).or_else(in_state(BreakoutState::Playing)),
+                    )
+                    // The pause button also closes the rebinding screen
+                    .run_if(not(resource_exists::<RebindingScreen>())),
             )
             .add_system(
-                finish_game
-                    .in_set(OnUpdate(BreakoutState::Playing))
-                    .run_if(bricks_cleared),
-            );
-
-        app.insert_resource(FixedTime::new(Duration::from_millis(1)))
-            .add_systems(
-                (
-                    ball_movement.in_set(OnUpdate(BreakoutState::Playing)),
-                    lives.run_if(on_event::<BottomCollisionEvent>()),
-                    brick_collision.run_if(on_event::<BrickCollisionEvent>()),
-                    apply_system_buffers,
-                )
-                    .chain()
-                    .in_schedule(CoreSchedule::FixedUpdate),
+                apply_pause
+                    .after(toggle_pause)
+                    .run_if(resource_changed::<Paused>()),
             );
     }
 }
@@ -0,0 +1,301 @@
+use bevy::prelude::*;
+
+use crate::{
+    actions::{Action, ActionState},
+    breakout::{
+        reset_serve_released, snapshot_inputs, spawn_bricks, Ball, BottomCollisionEvent,
+        BreakoutState, BrickCollisionEvent, BrickDestroyedEvent, LifeLostEvent,
+        PaddleCollisionEvent, PaddleInputs, PreviousTickInputs, ServeReleased, SimulationSet,
+        SplitBallsEvent, TickInputs, WallCollisionEvent, FONT_PATH,
+    },
+    level::campaign_loaded,
+    players::{select_game_mode, GameMode},
+    replay::Replay,
+    rng::GameRng,
+};
+
+/// How much faster than real time the replay plays while fast-forwarding
+const FAST_FORWARD_SPEED: f32 = 4.;
+
+/// Seconds of the game a single seek skips
+const SEEK_SECONDS: f32 = 5.;
+
+/// Most ticks simulated in a single frame while seeking, so the game stays responsive
+const SEEK_TICKS_PER_FRAME: usize = 5000;
+
+/// The replay being played back, and how far along it is
+#[derive(Resource)]
+struct Playback {
+    replay: Replay,
+    /// Index of the tick whose inputs are fed in next
+    next_tick: usize,
+    paused: bool,
+    fast_forward: bool,
+    /// Tick the playback is skipping to
+    seek_target: Option<usize>,
+}
+
+impl Playback {
+    fn ended(&self) -> bool {
+        self.next_tick >= self.replay.ticks.len()
+    }
+
+    fn seconds(&self, ticks: usize) -> f32 {
+        ticks as f32 / self.replay.config.tick_rate
+    }
+}
+
+#[derive(Component)]
+struct PlaybackText;
+
+/// Feed the recorded inputs to the simulation in place of the local player
+///
+/// The first tick waits for every level of the campaign to be loaded, so the game moves on from
+/// one level to the next on the same tick it did when it was recorded.
+fn feed_inputs(mut playback: ResMut<Playback>, mut inputs: ResMut<PaddleInputs>) {
+    let Some(tick_inputs) = playback.replay.ticks.get(playback.next_tick) else {
+        return;
+    };
+    inputs.0.clone_from(tick_inputs);
+    playback.next_tick += 1;
+}
+
+/// Play the recorded game mode, whatever mode the paddle is under
+fn replay_game_mode(playback: Res<Playback>, mut game_mode: ResMut<GameMode>) {
+    if *game_mode != playback.replay.game_mode {
+        *game_mode = playback.replay.game_mode;
+    }
+}
+
+fn reseed_rng(playback: Res<Playback>, mut rng: ResMut<GameRng>) {
+    *rng = GameRng::seeded(playback.replay.seed);
+}
+
+/// The recording starts at the serve that started the game, so the serve was let go of before
+/// it, whatever the inputs were before the replay or before rewinding it
+fn release_serve(mut serve_released: ResMut<ServeReleased>) {
+    serve_released.0 = true;
+}
+
+fn playback_controls(action_state: Res<ActionState>, mut playback: ResMut<Playback>) {
+    if action_state.just_pressed(Action::Pause) {
+        playback.paused = !playback.paused;
+    }
+    if action_state.just_pressed(Action::Serve) {
+        playback.fast_forward = !playback.fast_forward;
+    }
+
+    let seek_ticks = (SEEK_SECONDS * playback.replay.config.tick_rate) as usize;
+    let from = playback.seek_target.unwrap_or(playback.next_tick);
+    if action_state.just_pressed(Action::MoveLeft) {
+        playback.seek_target = Some(from.saturating_sub(seek_ticks));
+    }
+    if action_state.just_pressed(Action::MoveRight) {
+        playback.seek_target = Some((from + seek_ticks).min(playback.replay.ticks.len()));
+    }
+}
+
+/// Run the clock at the speed of the playback, and stop it at the end of the replay
+fn apply_playback_speed(playback: Res<Playback>, mut time: ResMut<Time>) {
+    if playback.paused || playback.ended() {
+        if !time.is_paused() {
+            time.pause();
+        }
+    } else if time.is_paused() {
+        time.unpause();
+    }
+
+    let speed = if playback.fast_forward {
+        FAST_FORWARD_SPEED
+    } else {
+        1.
+    };
+    if time.relative_speed() != speed {
+        time.set_relative_speed(speed);
+    }
+}
+
+/// Skip to another tick of the replay at once by simulating the ticks in between, from the start
+/// of the game when going back
+pub(crate) fn seek_to(world: &mut World, tick: usize) {
+    let mut playback = world
+        .get_resource_mut::<Playback>()
+        .expect("no replay is being played back");
+    playback.seek_target = Some(tick.min(playback.replay.ticks.len()));
+    while world.resource::<Playback>().seek_target.is_some() {
+        seek(world);
+    }
+}
+
+/// Skip a frame's worth of ticks towards the tick the playback is seeking to
+fn seek(world: &mut World) {
+    let playback = world.resource::<Playback>();
+    let Some(target) = playback.seek_target else {
+        return;
+    };
+    if target < playback.next_tick {
+        rewind(world);
+    }
+
+    for _ in 0..SEEK_TICKS_PER_FRAME {
+        let mut playback = world.resource_mut::<Playback>();
+        if playback.next_tick >= target {
+            playback.seek_target = None;
+            return;
+        }
+        world.run_schedule(CoreSchedule::FixedUpdate);
+    }
+}
+
+/// Put the game back the way it was before the replay started, the same way restarting a game
+/// does
+fn rewind(world: &mut World) {
+    let state = world.resource::<State<BreakoutState>>().0.clone();
+    if state != BreakoutState::Start {
+        world.run_schedule(OnExit(state.clone()));
+        // Leaving a finished game is what resets it
+        if state != BreakoutState::Finished {
+            world.run_schedule(OnExit(BreakoutState::Finished));
+        }
+        let balls: Vec<Entity> = world
+            .query_filtered::<Entity, With<Ball>>()
+            .iter(world)
+            .collect();
+        for ball in balls {
+            world.entity_mut(ball).despawn_recursive();
+        }
+        world.resource_mut::<State<BreakoutState>>().0 = BreakoutState::Start;
+        world.run_schedule(OnEnter(BreakoutState::Start));
+    }
+    world.resource_mut::<NextState<BreakoutState>>().0 = None;
+
+    world.resource_mut::<Events<BrickCollisionEvent>>().clear();
+    world.resource_mut::<Events<BrickDestroyedEvent>>().clear();
+    world.resource_mut::<Events<BottomCollisionEvent>>().clear();
+    world.resource_mut::<Events<PaddleCollisionEvent>>().clear();
+    world.resource_mut::<Events<WallCollisionEvent>>().clear();
+    world.resource_mut::<Events<LifeLostEvent>>().clear();
+    world.resource_mut::<Events<SplitBallsEvent>>().clear();
+
+    world.resource_mut::<PaddleInputs>().0 = vec![default()];
+    *world.resource_mut::<TickInputs>() = default();
+    *world.resource_mut::<PreviousTickInputs>() = default();
+    world.resource_mut::<Playback>().next_tick = 0;
+}
+
+fn setup_playback_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: 10.,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn((
+            Name::new("Playback"),
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Auto),
+                    position_type: PositionType::Absolute,
+                    position: UiRect::bottom(Val::Px(10.)),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Name::new("Playback text"),
+                PlaybackText,
+                TextBundle::from_sections([
+                    TextSection::new("", style.clone()),
+                    TextSection::new("\npause: pause  serve: 4x  left/right: seek", style),
+                ])
+                .with_text_alignment(TextAlignment::Center),
+            ));
+        });
+}
+
+fn update_playback_text(
+    playback: Res<Playback>,
+    mut text_query: Query<&mut Text, With<PlaybackText>>,
+) {
+    let position = playback.seek_target.unwrap_or(playback.next_tick);
+    let mut status = format!(
+        "replay {:.1}/{:.1}s",
+        playback.seconds(position),
+        playback.seconds(playback.replay.ticks.len()),
+    );
+    if playback.fast_forward {
+        status.push_str("  4x");
+    }
+    if playback.paused {
+        status.push_str("  paused");
+    }
+
+    for mut text in &mut text_query {
+        if text.sections[0].value != status {
+            text.sections[0].value = status.clone();
+        }
+    }
+}
+
+/// Play a recorded game back in place of the local player, with the config it was recorded with
+pub(crate) struct PlaybackPlugin {
+    pub(crate) replay: Replay,
+}
+
+impl Plugin for PlaybackPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ReplayInputsPlugin {
+            replay: self.replay.clone(),
+        })
+        .add_startup_system(setup_playback_text)
+        .add_systems((playback_controls, seek, apply_playback_speed).chain())
+        .add_system(update_playback_text.after(seek));
+    }
+}
+
+/// Feed a recorded game to the simulation tick by tick, without the controls and text of the
+/// playback
+pub(crate) struct ReplayInputsPlugin {
+    pub(crate) replay: Replay,
+}
+
+impl Plugin for ReplayInputsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.replay.config.clone())
+            .insert_resource(Playback {
+                replay: self.replay.clone(),
+                next_tick: 0,
+                paused: false,
+                fast_forward: false,
+                seek_target: None,
+            })
+            .add_system(
+                feed_inputs
+                    .before(snapshot_inputs)
+                    .in_set(SimulationSet::Input)
+                    .run_if(not(in_state(BreakoutState::Start)).or_else(campaign_loaded))
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                replay_game_mode
+                    .after(select_game_mode)
+                    .in_set(SimulationSet::Rules)
+                    .run_if(in_state(BreakoutState::Start))
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                reseed_rng
+                    .before(spawn_bricks)
+                    .in_schedule(OnExit(BreakoutState::Start)),
+            )
+            .add_system(
+                release_serve
+                    .after(reset_serve_released)
+                    .in_schedule(OnEnter(BreakoutState::Start)),
+            );
+    }
+}
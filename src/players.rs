@@ -0,0 +1,553 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    breakout::{
+        court_translation, spawn_brick, spawn_bricks, spawn_court, spawn_level_bricks,
+        spawn_paddle, BreakoutConfig, BreakoutState, Brick, BrickColor, Court, LifeLostEvent,
+        Lives, Paddle, PaddleInputs, Position, Score, SimulationSet, FONT_PATH,
+    },
+    collision::Collider,
+    level::{Campaign, CurrentCampaign, CurrentLevel, Level, LevelProgress},
+};
+
+/// Color of the game mode the paddle is on, and of the player whose turn it is
+pub(crate) const HIGHLIGHT_COLOR: Color = Color::YELLOW;
+
+/// How the game is played, picked at the start of every game
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameMode {
+    #[default]
+    SinglePlayer,
+    /// Two players take turns on the same controls, each with a wall of their own, and the turn
+    /// passes on whenever a life is lost
+    Alternating,
+    /// Two players at once with a paddle each, in their own half of the court, sharing the ball
+    /// and lives
+    CoOpSideBySide,
+    /// Two players at once with a paddle each, one above the other, sharing the ball and lives
+    CoOpStacked,
+    /// Two players against each other, each in a court of their own with their own ball, score
+    /// and lives
+    Versus,
+}
+
+impl GameMode {
+    /// Every game mode, in the order they are laid out across the court
+    pub(crate) const ALL: [GameMode; 5] = [
+        GameMode::SinglePlayer,
+        GameMode::Alternating,
+        GameMode::CoOpSideBySide,
+        GameMode::CoOpStacked,
+        GameMode::Versus,
+    ];
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            GameMode::SinglePlayer => "1P\nsolo",
+            GameMode::Alternating => "2P\nturns",
+            GameMode::CoOpSideBySide => "2P\nco-op",
+            GameMode::CoOpStacked => "2P\nstacked",
+            GameMode::Versus => "2P\nversus",
+        }
+    }
+
+    /// Where to put the paddle to pick this game mode, across a court of the given width
+    pub(crate) fn zone_center(&self, court_width: f32) -> f32 {
+        let index = GameMode::ALL.iter().position(|mode| mode == self).unwrap();
+        let zone_width = court_width / GameMode::ALL.len() as f32;
+        -court_width / 2. + (index as f32 + 0.5) * zone_width
+    }
+
+    /// Number of players with a score and lives of their own
+    ///
+    /// Players playing in the same court at the same time share theirs.
+    pub(crate) fn player_count(&self) -> usize {
+        match self {
+            GameMode::Alternating | GameMode::Versus => 2,
+            _ => 1,
+        }
+    }
+
+    /// Whether the players take turns in the same court
+    pub(crate) fn takes_turns(&self) -> bool {
+        *self == GameMode::Alternating
+    }
+
+    /// Number of courts side by side
+    pub(crate) fn court_count(&self) -> usize {
+        match self {
+            GameMode::Versus => 2,
+            _ => 1,
+        }
+    }
+
+    /// The paddles in the court with this index, one for every player playing in it at the same
+    /// time
+    pub(crate) fn paddles(&self, court: usize) -> Vec<Paddle> {
+        match self {
+            GameMode::SinglePlayer | GameMode::Alternating => vec![default()],
+            GameMode::Versus => vec![Paddle {
+                input: court,
+                ..default()
+            }],
+            GameMode::CoOpSideBySide => vec![
+                Paddle {
+                    lane: [0., 0.5],
+                    ..default()
+                },
+                Paddle {
+                    input: 1,
+                    lane: [0.5, 1.],
+                    ..default()
+                },
+            ],
+            GameMode::CoOpStacked => vec![
+                default(),
+                Paddle {
+                    input: 1,
+                    row: 1,
+                    ..default()
+                },
+            ],
+        }
+    }
+}
+
+/// A brick that was left standing when a player's turn ended
+#[derive(Clone)]
+struct SavedBrick {
+    brick: Brick,
+    color: Color,
+    position: Vec2,
+    size: Vec2,
+}
+
+/// How far a player got, kept up to date for the player whose turn it is
+#[derive(Clone)]
+pub(crate) struct Player {
+    pub(crate) score: u32,
+    pub(crate) lives: u32,
+    /// Index of their level within the campaign
+    pub(crate) level: usize,
+    /// Bricks left when their last turn ended, or `None` before their first turn
+    bricks: Option<Vec<SavedBrick>>,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Self {
+            score: 0,
+            lives: Lives::default().0,
+            level: 0,
+            bricks: None,
+        }
+    }
+}
+
+impl Player {
+    /// Score with the bonus for the lives left
+    pub(crate) fn final_score(&self) -> u32 {
+        self.score + self.lives * 100
+    }
+}
+
+/// The players of the current game
+///
+/// `Score`, `Lives` and `LevelProgress` belong to the player whose turn it is, and are copied
+/// here every tick. In versus that is always the first player, and the second one keeps their
+/// score and lives here.
+#[derive(Resource)]
+pub(crate) struct Players {
+    /// Index of the player whose turn it is
+    pub(crate) current: usize,
+    pub(crate) players: Vec<Player>,
+}
+
+impl Default for Players {
+    fn default() -> Self {
+        Self {
+            current: 0,
+            players: vec![default()],
+        }
+    }
+}
+
+impl Players {
+    pub(crate) fn is_multiplayer(&self) -> bool {
+        self.players.len() > 1
+    }
+
+    /// Whether a player waiting for their turn has lives left
+    pub(crate) fn others_have_lives(&self) -> bool {
+        self.players
+            .iter()
+            .enumerate()
+            .any(|(index, player)| index != self.current && player.lives > 0)
+    }
+}
+
+pub(crate) fn versus(game_mode: Res<GameMode>) -> bool {
+    *game_mode == GameMode::Versus
+}
+
+/// The score and lives of the player in each court
+///
+/// Whoever's turn it is plays in the first court, with their score and lives in `Score` and
+/// `Lives`, and every other court belongs to the player with the same index.
+#[derive(SystemParam)]
+pub(crate) struct CourtPlayers<'w> {
+    score: ResMut<'w, Score>,
+    lives: ResMut<'w, Lives>,
+    players: ResMut<'w, Players>,
+}
+
+impl CourtPlayers<'_> {
+    pub(crate) fn score_mut(&mut self, court: usize) -> &mut u32 {
+        match court {
+            0 => &mut self.score.0,
+            _ => &mut self.players.players[court].score,
+        }
+    }
+
+    pub(crate) fn lives_mut(&mut self, court: usize) -> &mut u32 {
+        match court {
+            0 => &mut self.lives.0,
+            _ => &mut self.players.players[court].lives,
+        }
+    }
+}
+
+/// The court the player whose turn it is plays in
+fn first_court(court_query: &Query<(Entity, &Court)>) -> Option<Entity> {
+    court_query
+        .iter()
+        .find(|(_, court)| court.index == 0)
+        .map(|(entity, _)| entity)
+}
+
+/// Everything that is shown while the game mode is picked
+#[derive(Component)]
+pub(crate) struct GameModeText;
+
+/// The label of a game mode, above the part of the court the paddle picks it in
+#[derive(Component)]
+pub(crate) struct GameModeLabel(GameMode);
+
+/// Lay the game modes out across the court, for the paddle to pick one
+pub(crate) fn show_game_modes(
+    mut commands: Commands,
+    config: Res<BreakoutConfig>,
+    asset_server: Res<AssetServer>,
+    court_query: Query<(Entity, &Court)>,
+) {
+    let Some(court_entity) = first_court(&court_query) else {
+        return;
+    };
+    let style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: 8.,
+        color: Color::WHITE,
+    };
+
+    commands.entity(court_entity).with_children(|parent| {
+        for mode in GameMode::ALL {
+            let x = mode.zone_center(config.court_size[0]);
+            parent.spawn((
+                GameModeText,
+                GameModeLabel(mode),
+                Name::new("Game mode label"),
+                Text2dBundle {
+                    text: Text::from_section(mode.label(), style.clone())
+                        .with_alignment(TextAlignment::Center),
+                    transform: Transform::from_translation(Vec3::new(x, 0., 1.)),
+                    ..default()
+                },
+            ));
+        }
+
+        parent.spawn((
+            GameModeText,
+            Name::new("Game mode prompt"),
+            Text2dBundle {
+                text: Text::from_section("move to pick, serve to start", style)
+                    .with_alignment(TextAlignment::Center),
+                transform: Transform::from_translation(Vec3::new(
+                    0.,
+                    -config.court_size[1] / 4.,
+                    1.,
+                )),
+                ..default()
+            },
+        ));
+    });
+}
+
+/// Pick the game mode the paddle is under
+pub(crate) fn select_game_mode(
+    config: Res<BreakoutConfig>,
+    mut game_mode: ResMut<GameMode>,
+    paddle_query: Query<&Position, With<Paddle>>,
+) {
+    let Ok(paddle_position) = paddle_query.get_single() else {
+        return;
+    };
+    let across = (paddle_position.x / config.court_size[0] + 0.5).clamp(0., 1.);
+    let index = ((across * GameMode::ALL.len() as f32) as usize).min(GameMode::ALL.len() - 1);
+    let mode = GameMode::ALL[index];
+    if *game_mode != mode {
+        *game_mode = mode;
+    }
+}
+
+pub(crate) fn highlight_game_mode(
+    game_mode: Res<GameMode>,
+    mut label_query: Query<(&GameModeLabel, &mut Text)>,
+) {
+    for (label, mut text) in &mut label_query {
+        let color = if label.0 == *game_mode {
+            HIGHLIGHT_COLOR
+        } else {
+            Color::WHITE
+        };
+        if text.sections[0].style.color != color {
+            text.sections[0].style.color = color;
+        }
+    }
+}
+
+/// Seat the players of the picked game mode
+fn start_players(game_mode: Res<GameMode>, mut players: ResMut<Players>) {
+    *players = Players {
+        current: 0,
+        players: vec![default(); game_mode.player_count()],
+    };
+}
+
+/// Replace the courts and paddles with those of a game mode, and make room in `PaddleInputs` for
+/// every paddle
+///
+/// The first court stays, the others come and go with the game mode.
+fn place_courts(
+    commands: &mut Commands,
+    config: &BreakoutConfig,
+    game_mode: GameMode,
+    court_query: &mut Query<(Entity, &mut Court, &mut Transform)>,
+    paddle_query: &Query<(Entity, &Parent), With<Paddle>>,
+    inputs: &mut PaddleInputs,
+) {
+    let court_count = game_mode.court_count();
+    let mut courts = Vec::new();
+    let mut removed_courts = Vec::new();
+    for (court_entity, mut court, mut transform) in court_query {
+        if court.index == 0 {
+            court.ball_speed_scale = 1.;
+            transform.translation = court_translation(config, 0, court_count);
+            courts.push(court_entity);
+        } else {
+            commands.entity(court_entity).despawn_recursive();
+            removed_courts.push(court_entity);
+        }
+    }
+    for (paddle_entity, parent) in paddle_query {
+        // Paddles of removed courts go along with them
+        if !removed_courts.contains(&parent.get()) {
+            commands.entity(paddle_entity).despawn_recursive();
+        }
+    }
+    courts.extend((1..court_count).map(|index| spawn_court(commands, config, index, court_count)));
+
+    let input_count = (0..court_count)
+        .flat_map(|court| game_mode.paddles(court))
+        .map(|paddle| paddle.input + 1)
+        .max()
+        .unwrap_or(1);
+    inputs.resize(input_count, default());
+
+    for (index, court_entity) in courts.into_iter().enumerate() {
+        commands.entity(court_entity).with_children(|parent| {
+            for paddle in game_mode.paddles(index) {
+                spawn_paddle(parent, config, paddle);
+            }
+        });
+    }
+}
+
+/// Set up the courts and paddles of the picked game mode
+fn arrange_courts(
+    mut commands: Commands,
+    config: Res<BreakoutConfig>,
+    game_mode: Res<GameMode>,
+    mut inputs: ResMut<PaddleInputs>,
+    mut court_query: Query<(Entity, &mut Court, &mut Transform)>,
+    paddle_query: Query<(Entity, &Parent), With<Paddle>>,
+) {
+    place_courts(
+        &mut commands,
+        &config,
+        *game_mode,
+        &mut court_query,
+        &paddle_query,
+        &mut inputs,
+    );
+}
+
+/// Go back to the single court and paddle the next game mode is picked with
+fn reset_courts(
+    mut commands: Commands,
+    config: Res<BreakoutConfig>,
+    mut inputs: ResMut<PaddleInputs>,
+    mut court_query: Query<(Entity, &mut Court, &mut Transform)>,
+    paddle_query: Query<(Entity, &Parent), With<Paddle>>,
+) {
+    place_courts(
+        &mut commands,
+        &config,
+        GameMode::SinglePlayer,
+        &mut court_query,
+        &paddle_query,
+        &mut inputs,
+    );
+}
+
+/// Keep the player whose turn it is up to date with how they are doing
+fn record_current_player(
+    score: Res<Score>,
+    lives: Res<Lives>,
+    progress: Res<LevelProgress>,
+    mut players: ResMut<Players>,
+) {
+    let current = players.current;
+    let player = &players.players[current];
+    if player.score != score.0 || player.lives != lives.0 || player.level != progress.0 {
+        let player = &mut players.players[current];
+        player.score = score.0;
+        player.lives = lives.0;
+        player.level = progress.0;
+    }
+}
+
+/// Pass the turn on to the next player with lives left after a life is lost, putting away the
+/// bricks of the player whose turn ended and bringing back theirs
+fn switch_turn(
+    mut commands: Commands,
+    config: Res<BreakoutConfig>,
+    mut life_lost_events: EventReader<LifeLostEvent>,
+    mut players: ResMut<Players>,
+    mut score: ResMut<Score>,
+    mut lives: ResMut<Lives>,
+    mut progress: ResMut<LevelProgress>,
+    current_campaign: Res<CurrentCampaign>,
+    campaigns: Res<Assets<Campaign>>,
+    levels: Res<Assets<Level>>,
+    mut current_level: ResMut<CurrentLevel>,
+    court_query: Query<(Entity, &Court)>,
+    brick_query: Query<(Entity, &Brick, &BrickColor, &Transform, &Collider)>,
+) {
+    if life_lost_events.iter().count() == 0 {
+        return;
+    }
+
+    let player_count = players.players.len();
+    let Some(next) = (1..player_count)
+        .map(|offset| (players.current + offset) % player_count)
+        .find(|index| players.players[*index].lives > 0)
+    else {
+        return;
+    };
+
+    let mut saved_bricks: Vec<(Entity, SavedBrick)> = brick_query
+        .iter()
+        .map(|(entity, brick, brick_color, transform, collider)| {
+            let saved_brick = SavedBrick {
+                brick: brick.clone(),
+                color: brick_color.0,
+                position: transform.translation.truncate(),
+                size: collider.get_size(),
+            };
+            (entity, saved_brick)
+        })
+        .collect();
+    // Keep the bricks in spawn order, so they come back the way they were
+    saved_bricks.sort_by_key(|(entity, _)| *entity);
+    for (entity, _) in &saved_bricks {
+        commands.entity(*entity).despawn_recursive();
+    }
+
+    let current = players.current;
+    let player = &mut players.players[current];
+    player.score = score.0;
+    player.lives = lives.0;
+    player.level = progress.0;
+    player.bricks = Some(saved_bricks.into_iter().map(|(_, brick)| brick).collect());
+
+    players.current = next;
+    let player = &mut players.players[next];
+    score.0 = player.score;
+    lives.0 = player.lives;
+    progress.0 = player.level;
+
+    if let Some(level) = campaigns
+        .get(&current_campaign.0)
+        .and_then(|campaign| campaign.levels.get(player.level))
+    {
+        current_level.0 = level.clone();
+    }
+
+    let Some(court_entity) = first_court(&court_query) else {
+        return;
+    };
+    match player.bricks.take() {
+        Some(bricks) => {
+            commands.entity(court_entity).with_children(|parent| {
+                for saved_brick in bricks {
+                    spawn_brick(
+                        parent,
+                        saved_brick.brick,
+                        saved_brick.color,
+                        saved_brick.position,
+                        saved_brick.size,
+                    );
+                }
+            });
+        }
+        None => {
+            if let Some(level) = levels.get(&current_level.0) {
+                spawn_level_bricks(&mut commands, &config, level, court_entity);
+            }
+        }
+    }
+}
+
+pub(crate) struct PlayersPlugin;
+
+impl Plugin for PlayersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameMode>()
+            .init_resource::<Players>()
+            .add_system(reset_courts.in_schedule(OnEnter(BreakoutState::Start)))
+            .add_systems(
+                (
+                    start_players,
+                    arrange_courts,
+                    // The bricks go into the courts of the game mode
+                    apply_system_buffers,
+                )
+                    .chain()
+                    .before(spawn_bricks)
+                    .in_schedule(OnExit(BreakoutState::Start)),
+            )
+            .add_system(switch_turn.in_schedule(OnEnter(BreakoutState::Serve)))
+            .add_system(
+                select_game_mode
+                    .run_if(in_state(BreakoutState::Start))
+                    .in_set(SimulationSet::Rules)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                record_current_player
+                    .in_set(SimulationSet::Transition)
+                    .before(apply_state_transition::<BreakoutState>)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+    }
+}
@@ -0,0 +1,564 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    breakout::{
+        ball_movement, brick_collision, paddle_movement, resize_paddles, serve_just_pressed_on,
+        serve_pressed_on, Ball, BallSpeedScale, BreakoutConfig, BreakoutState, Brick,
+        BrickCollisionEvent, BrickDestroyedEvent, Court, LifeLostEvent, Lives, Paddle,
+        PaddleCollisionEvent, PaddleWidthScale, Position, PreviousTickInputs, SimulationSet,
+        SplitBallsEvent, TickInputs, Velocity,
+    },
+    collision::{penetration_aabb, sweep_aabb, Collider},
+    players::versus,
+    rng::GameRng,
+    util::despawn_with,
+};
+
+/// Tunable parameters of the power-ups, with durations in seconds
+#[derive(Reflect, FromReflect, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub(crate) struct PowerUpConfig {
+    /// Chance that a destroyed brick drops a power-up, from 0 to 1
+    pub(crate) drop_chance: f32,
+    pub(crate) capsule_size: [f32; 2],
+    pub(crate) fall_speed: f32,
+    /// How much wider the wide paddle is
+    pub(crate) wide_paddle_scale: f32,
+    pub(crate) wide_paddle_duration: f32,
+    /// How much slower the ball travels with the slow ball
+    pub(crate) slow_ball_scale: f32,
+    pub(crate) slow_ball_duration: f32,
+    pub(crate) sticky_paddle_duration: f32,
+    pub(crate) laser_duration: f32,
+    /// Time between two laser shots while serve is held
+    pub(crate) laser_cooldown: f32,
+    pub(crate) laser_speed: f32,
+    pub(crate) laser_size: [f32; 2],
+    /// Number of balls every ball is split into by the multi-ball
+    pub(crate) multi_ball_count: usize,
+}
+
+impl Default for PowerUpConfig {
+    fn default() -> Self {
+        Self {
+            drop_chance: 0.15,
+            capsule_size: [16., 8.],
+            fall_speed: 100.,
+            wide_paddle_scale: 1.5,
+            wide_paddle_duration: 15.,
+            slow_ball_scale: 0.6,
+            slow_ball_duration: 10.,
+            sticky_paddle_duration: 15.,
+            laser_duration: 10.,
+            laser_cooldown: 0.3,
+            laser_speed: 600.,
+            laser_size: [2., 8.],
+            multi_ball_count: 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PowerUpKind {
+    WidePaddle,
+    SlowBall,
+    ExtraLife,
+    StickyPaddle,
+    Laser,
+    MultiBall,
+}
+
+/// Every kind of power-up, equally likely to drop
+const POWER_UP_KINDS: [PowerUpKind; 6] = [
+    PowerUpKind::WidePaddle,
+    PowerUpKind::SlowBall,
+    PowerUpKind::ExtraLife,
+    PowerUpKind::StickyPaddle,
+    PowerUpKind::Laser,
+    PowerUpKind::MultiBall,
+];
+
+impl PowerUpKind {
+    /// How long the effect lasts, or `None` for effects that apply once
+    fn duration(&self, config: &PowerUpConfig) -> Option<f32> {
+        match self {
+            PowerUpKind::WidePaddle => Some(config.wide_paddle_duration),
+            PowerUpKind::SlowBall => Some(config.slow_ball_duration),
+            PowerUpKind::ExtraLife => None,
+            PowerUpKind::StickyPaddle => Some(config.sticky_paddle_duration),
+            PowerUpKind::Laser => Some(config.laser_duration),
+            PowerUpKind::MultiBall => None,
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            PowerUpKind::WidePaddle => Color::rgb(0.25, 0.5, 1.),
+            PowerUpKind::SlowBall => Color::rgb(1., 0.65, 0.),
+            PowerUpKind::ExtraLife => Color::rgb(0.5, 0.5, 0.5),
+            PowerUpKind::StickyPaddle => Color::rgb(0., 1., 0.),
+            PowerUpKind::Laser => Color::rgb(1., 0., 0.),
+            PowerUpKind::MultiBall => Color::rgb(1., 0., 1.),
+        }
+    }
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            PowerUpKind::WidePaddle => "wide",
+            PowerUpKind::SlowBall => "slow",
+            PowerUpKind::ExtraLife => "life",
+            PowerUpKind::StickyPaddle => "sticky",
+            PowerUpKind::Laser => "laser",
+            PowerUpKind::MultiBall => "multi",
+        }
+    }
+}
+
+/// A falling capsule that applies its power-up when a paddle catches it
+#[derive(Component)]
+pub(crate) struct PowerUp(pub(crate) PowerUpKind);
+
+#[derive(Component)]
+pub(crate) struct LaserBolt;
+
+/// A ball held by the sticky paddle until serve is pressed
+#[derive(Component)]
+pub(crate) struct StuckToPaddle {
+    paddle: Entity,
+    /// Horizontal offset from the center of the paddle
+    offset: f32,
+    /// Velocity the ball is released with
+    velocity: Vec2,
+}
+
+pub(crate) struct ActivePowerUp {
+    pub(crate) kind: PowerUpKind,
+    pub(crate) timer: Timer,
+}
+
+/// The timed power-ups that are currently in effect, in the order they were caught
+#[derive(Resource, Default)]
+pub(crate) struct ActivePowerUps(pub(crate) Vec<ActivePowerUp>);
+
+impl ActivePowerUps {
+    pub(crate) fn is_active(&self, kind: PowerUpKind) -> bool {
+        self.0.iter().any(|active| active.kind == kind)
+    }
+
+    /// Start an effect, or restart it if it is already active
+    fn activate(&mut self, kind: PowerUpKind, duration: f32) {
+        self.0.retain(|active| active.kind != kind);
+        self.0.push(ActivePowerUp {
+            kind,
+            timer: Timer::from_seconds(duration, TimerMode::Once),
+        });
+    }
+}
+
+/// Time left until the laser can fire again
+#[derive(Resource, Default, Deref, DerefMut)]
+pub(crate) struct LaserCooldown(Timer);
+
+fn sticky_paddle_active(active_power_ups: Res<ActivePowerUps>) -> bool {
+    active_power_ups.is_active(PowerUpKind::StickyPaddle)
+}
+
+fn laser_active(active_power_ups: Res<ActivePowerUps>) -> bool {
+    active_power_ups.is_active(PowerUpKind::Laser)
+}
+
+/// Let destroyed bricks drop a random power-up now and then
+pub(crate) fn drop_power_ups(
+    mut commands: Commands,
+    config: Res<BreakoutConfig>,
+    mut rng: ResMut<GameRng>,
+    mut brick_destroyed_events: EventReader<BrickDestroyedEvent>,
+) {
+    let capsule_size = Vec2::from_array(config.power_ups.capsule_size);
+
+    for destroyed in brick_destroyed_events.iter() {
+        if !rng.gen_bool(config.power_ups.drop_chance.clamp(0., 1.) as f64) {
+            continue;
+        }
+        let kind = POWER_UP_KINDS[rng.gen_range(0..POWER_UP_KINDS.len())];
+
+        commands.entity(destroyed.court).with_children(|parent| {
+            parent.spawn((
+                PowerUp(kind),
+                Name::new("Power-up"),
+                Position(destroyed.position),
+                Velocity(Vec2::new(0., -config.power_ups.fall_speed)),
+                Collider::new(capsule_size.x, capsule_size.y),
+                SpriteBundle {
+                    transform: Transform::from_translation(destroyed.position.extend(1.)),
+                    sprite: Sprite {
+                        color: kind.color(),
+                        custom_size: Some(capsule_size),
+                        ..default()
+                    },
+                    ..default()
+                },
+            ));
+        });
+    }
+}
+
+pub(crate) fn power_up_movement(
+    fixed_time: Res<FixedTime>,
+    mut power_up_query: Query<(&mut Position, &Velocity), With<PowerUp>>,
+) {
+    let delta_seconds = fixed_time.period.as_secs_f32();
+    for (mut position, velocity) in &mut power_up_query {
+        position.0 += velocity.0 * delta_seconds;
+    }
+}
+
+/// Apply the power-ups the paddles catch, and drop the ones that fall out of their court
+pub(crate) fn catch_power_ups(
+    mut commands: Commands,
+    config: Res<BreakoutConfig>,
+    mut active_power_ups: ResMut<ActivePowerUps>,
+    mut lives: ResMut<Lives>,
+    mut split_balls_events: EventWriter<SplitBallsEvent>,
+    court_query: Query<&Collider, With<Court>>,
+    paddle_query: Query<(&Position, &Collider, &Parent), With<Paddle>>,
+    power_up_query: Query<(Entity, &PowerUp, &Position, &Collider, &Parent)>,
+) {
+    for (power_up_entity, power_up, position, collider, power_up_parent) in &power_up_query {
+        let Ok(court_collider) = court_query.get(power_up_parent.get()) else {
+            continue;
+        };
+        let court_half_size = court_collider.get_half_size();
+        let caught = paddle_query
+            .iter()
+            .filter(|(.., paddle_parent)| paddle_parent.get() == power_up_parent.get())
+            .any(|(paddle_position, paddle_collider, _)| {
+                penetration_aabb(
+                    position.0,
+                    collider.get_half_size(),
+                    paddle_position.0,
+                    paddle_collider.get_half_size(),
+                )
+                .is_some()
+            });
+
+        if caught {
+            let kind = power_up.0;
+            match kind {
+                PowerUpKind::ExtraLife => lives.0 += 1,
+                PowerUpKind::MultiBall => split_balls_events.send(SplitBallsEvent {
+                    balls: config.power_ups.multi_ball_count,
+                }),
+                _ => {
+                    if let Some(duration) = kind.duration(&config.power_ups) {
+                        active_power_ups.activate(kind, duration);
+                    }
+                }
+            }
+            commands.entity(power_up_entity).despawn_recursive();
+        } else if position.y + collider.get_half_size().y < -court_half_size.y {
+            commands.entity(power_up_entity).despawn_recursive();
+        }
+    }
+}
+
+pub(crate) fn tick_power_ups(
+    fixed_time: Res<FixedTime>,
+    mut active_power_ups: ResMut<ActivePowerUps>,
+) {
+    for active in &mut active_power_ups.0 {
+        active.timer.tick(fixed_time.period);
+    }
+    active_power_ups.0.retain(|active| !active.timer.finished());
+}
+
+/// Scale the paddle and ball for the wide paddle and slow ball
+pub(crate) fn apply_power_ups(
+    config: Res<BreakoutConfig>,
+    active_power_ups: Res<ActivePowerUps>,
+    mut paddle_width_scale: ResMut<PaddleWidthScale>,
+    mut ball_speed_scale: ResMut<BallSpeedScale>,
+) {
+    let width_scale = if active_power_ups.is_active(PowerUpKind::WidePaddle) {
+        config.power_ups.wide_paddle_scale
+    } else {
+        1.
+    };
+    if paddle_width_scale.0 != width_scale {
+        paddle_width_scale.0 = width_scale;
+    }
+
+    let speed_scale = if active_power_ups.is_active(PowerUpKind::SlowBall) {
+        config.power_ups.slow_ball_scale
+    } else {
+        1.
+    };
+    if ball_speed_scale.0 != speed_scale {
+        ball_speed_scale.0 = speed_scale;
+    }
+}
+
+/// Catch balls on the sticky paddle when they bounce off it
+pub(crate) fn stick_balls(
+    mut commands: Commands,
+    mut paddle_collision_events: EventReader<PaddleCollisionEvent>,
+    mut ball_query: Query<(&Position, &mut Velocity), (With<Ball>, Without<StuckToPaddle>)>,
+    paddle_query: Query<&Position, With<Paddle>>,
+) {
+    for event in paddle_collision_events.iter() {
+        let Ok((ball_position, mut ball_velocity)) = ball_query.get_mut(event.ball_entity) else {
+            continue;
+        };
+        let Ok(paddle_position) = paddle_query.get(event.paddle_entity) else {
+            continue;
+        };
+
+        commands.entity(event.ball_entity).insert(StuckToPaddle {
+            paddle: event.paddle_entity,
+            offset: ball_position.x - paddle_position.x,
+            velocity: ball_velocity.0,
+        });
+        ball_velocity.0 = Vec2::ZERO;
+    }
+}
+
+/// Send stuck balls off again, with the velocity they had when they got stuck, once the player
+/// of their paddle serves or the sticky paddle wears off
+pub(crate) fn release_stuck_balls(
+    mut commands: Commands,
+    active_power_ups: Res<ActivePowerUps>,
+    tick_inputs: Res<TickInputs>,
+    previous_tick_inputs: Res<PreviousTickInputs>,
+    mut ball_query: Query<(Entity, &StuckToPaddle, &mut Velocity)>,
+    paddle_query: Query<&Paddle>,
+) {
+    let sticky = active_power_ups.is_active(PowerUpKind::StickyPaddle);
+    for (ball_entity, stuck, mut velocity) in &mut ball_query {
+        let released = !sticky
+            || paddle_query.get(stuck.paddle).map_or(true, |paddle| {
+                serve_just_pressed_on(&tick_inputs, &previous_tick_inputs, paddle.input)
+            });
+        if !released {
+            continue;
+        }
+
+        velocity.0 = stuck.velocity;
+        commands.entity(ball_entity).remove::<StuckToPaddle>();
+    }
+}
+
+/// Keep stuck balls on top of their paddle
+pub(crate) fn hold_stuck_balls(
+    mut ball_query: Query<(&StuckToPaddle, &mut Position, &Collider), Without<Paddle>>,
+    paddle_query: Query<(&Position, &Collider), With<Paddle>>,
+) {
+    for (stuck, mut ball_position, ball_collider) in &mut ball_query {
+        let Ok((paddle_position, paddle_collider)) = paddle_query.get(stuck.paddle) else {
+            continue;
+        };
+        let paddle_half_size = paddle_collider.get_half_size();
+        let ball_half_size = ball_collider.get_half_size();
+
+        ball_position.x =
+            paddle_position.x + stuck.offset.clamp(-paddle_half_size.x, paddle_half_size.x);
+        ball_position.y = paddle_position.y + paddle_half_size.y + ball_half_size.y + 0.1;
+    }
+}
+
+/// Shoot a pair of laser bolts from the edges of every paddle whose player holds serve
+pub(crate) fn fire_lasers(
+    mut commands: Commands,
+    config: Res<BreakoutConfig>,
+    fixed_time: Res<FixedTime>,
+    tick_inputs: Res<TickInputs>,
+    mut cooldown: ResMut<LaserCooldown>,
+    paddle_query: Query<(&Paddle, &Position, &Collider, &Parent)>,
+) {
+    let mut firing_paddles = paddle_query
+        .iter()
+        .filter(|(paddle, ..)| serve_pressed_on(&tick_inputs, paddle.input))
+        .peekable();
+    if firing_paddles.peek().is_none() {
+        return;
+    }
+    if !cooldown.tick(fixed_time.period).finished() {
+        return;
+    }
+    cooldown.0 = Timer::from_seconds(config.power_ups.laser_cooldown, TimerMode::Once);
+
+    let laser_size = Vec2::from_array(config.power_ups.laser_size);
+    for (_, paddle_position, paddle_collider, parent) in firing_paddles {
+        let paddle_half_size = paddle_collider.get_half_size();
+        for side in [-1., 1.] {
+            let position = paddle_position.0
+                + Vec2::new(
+                    side * (paddle_half_size.x - laser_size.x),
+                    paddle_half_size.y + laser_size.y / 2.,
+                );
+            commands.entity(parent.get()).with_children(|parent| {
+                parent.spawn((
+                    LaserBolt,
+                    Name::new("Laser bolt"),
+                    Position(position),
+                    Velocity(Vec2::new(0., config.power_ups.laser_speed)),
+                    Collider::new(laser_size.x, laser_size.y),
+                    SpriteBundle {
+                        transform: Transform::from_translation(position.extend(1.)),
+                        sprite: Sprite {
+                            color: PowerUpKind::Laser.color(),
+                            custom_size: Some(laser_size),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                ));
+            });
+        }
+    }
+}
+
+/// Move laser bolts up until they hit a brick or leave their court
+pub(crate) fn laser_movement(
+    mut commands: Commands,
+    fixed_time: Res<FixedTime>,
+    mut brick_collision_events: EventWriter<BrickCollisionEvent>,
+    court_query: Query<&Collider, With<Court>>,
+    mut laser_query: Query<
+        (Entity, &mut Position, &Velocity, &Collider, &Parent),
+        (With<LaserBolt>, Without<Court>),
+    >,
+    brick_query: Query<(Entity, &Transform, &Collider, &Parent), With<Brick>>,
+) {
+    let delta_seconds = fixed_time.period.as_secs_f32();
+
+    // Resolve bolts in a fixed order, like the balls
+    let mut laser_entities: Vec<Entity> = laser_query.iter().map(|(entity, ..)| entity).collect();
+    laser_entities.sort();
+
+    for laser_entity in laser_entities {
+        let (_, mut position, velocity, collider, laser_parent) =
+            laser_query.get_mut(laser_entity).unwrap();
+        let Ok(court_collider) = court_query.get(laser_parent.get()) else {
+            continue;
+        };
+        let court_half_size = court_collider.get_half_size();
+        let half_size = collider.get_half_size();
+        let displacement = velocity.0 * delta_seconds;
+
+        // The first brick along the way takes the hit
+        let hit_brick = brick_query
+            .iter()
+            .filter(|(.., brick_parent)| brick_parent.get() == laser_parent.get())
+            .filter_map(|(brick_entity, brick_transform, brick_collider, _)| {
+                let brick_position = brick_transform.translation.truncate();
+                let brick_half_size = brick_collider.get_half_size();
+                if penetration_aabb(position.0, half_size, brick_position, brick_half_size)
+                    .is_some()
+                {
+                    return Some((brick_entity, 0.));
+                }
+                sweep_aabb(
+                    position.0,
+                    half_size,
+                    displacement,
+                    brick_position,
+                    brick_half_size,
+                )
+                .map(|hit| (brick_entity, hit.time))
+            })
+            .min_by(|(a_entity, a), (b_entity, b)| a.total_cmp(b).then(a_entity.cmp(b_entity)));
+
+        if let Some((brick_entity, _)) = hit_brick {
+            brick_collision_events.send(BrickCollisionEvent { brick_entity });
+            commands.entity(laser_entity).despawn_recursive();
+            continue;
+        }
+
+        position.0 += displacement;
+        if position.y - half_size.y > court_half_size.y {
+            commands.entity(laser_entity).despawn_recursive();
+        }
+    }
+}
+
+/// Remove every power-up from play and end all effects
+pub(crate) fn clear_power_ups(
+    mut commands: Commands,
+    mut active_power_ups: ResMut<ActivePowerUps>,
+    mut cooldown: ResMut<LaserCooldown>,
+    power_up_query: Query<Entity, Or<(With<PowerUp>, With<LaserBolt>)>>,
+    mut stuck_ball_query: Query<(Entity, &StuckToPaddle, &mut Velocity)>,
+) {
+    for entity in &power_up_query {
+        commands.entity(entity).despawn_recursive();
+    }
+    for (ball_entity, stuck, mut velocity) in &mut stuck_ball_query {
+        velocity.0 = stuck.velocity;
+        commands.entity(ball_entity).remove::<StuckToPaddle>();
+    }
+    active_power_ups.0.clear();
+    *cooldown = default();
+}
+
+pub(crate) struct PowerUpPlugin;
+
+impl Plugin for PowerUpPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActivePowerUps>()
+            .init_resource::<LaserCooldown>()
+            .add_systems(
+                (despawn_with::<PowerUp>, despawn_with::<LaserBolt>)
+                    .in_schedule(OnEnter(BreakoutState::LevelCleared)),
+            )
+            .add_system(clear_power_ups.in_schedule(OnExit(BreakoutState::Finished)))
+            .add_systems(
+                (
+                    release_stuck_balls.run_if(in_state(BreakoutState::Playing)),
+                    apply_system_buffers,
+                    hold_stuck_balls.run_if(in_state(BreakoutState::Playing)),
+                )
+                    .chain()
+                    .after(paddle_movement)
+                    .before(ball_movement)
+                    .in_set(SimulationSet::Movement)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_systems(
+                (
+                    fire_lasers
+                        .run_if(in_state(BreakoutState::Playing))
+                        .run_if(laser_active),
+                    laser_movement.run_if(in_state(BreakoutState::Playing)),
+                    power_up_movement.run_if(
+                        in_state(BreakoutState::Playing).or_else(in_state(BreakoutState::Serve)),
+                    ),
+                )
+                    .chain()
+                    .after(ball_movement)
+                    .in_set(SimulationSet::Movement)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_systems(
+                (
+                    // Power-ups would help both players in versus, whoever catches them
+                    drop_power_ups
+                        .run_if(not(versus))
+                        .run_if(on_event::<BrickDestroyedEvent>()),
+                    catch_power_ups,
+                    tick_power_ups.run_if(in_state(BreakoutState::Playing)),
+                    stick_balls
+                        .run_if(sticky_paddle_active)
+                        .run_if(on_event::<PaddleCollisionEvent>()),
+                    clear_power_ups.run_if(on_event::<LifeLostEvent>()),
+                    apply_power_ups,
+                )
+                    .chain()
+                    .after(brick_collision)
+                    .before(resize_paddles)
+                    .in_set(SimulationSet::Rules)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+    }
+}
@@ -0,0 +1,225 @@
+use bevy::prelude::*;
+
+use crate::{
+    actions::{Action, Binding, Bindings},
+    breakout::FONT_PATH,
+    local::game_paused,
+};
+
+/// The rebinding screen uses fixed keys of its own, so no binding can lock players out of it
+const OPEN_KEY: KeyCode = KeyCode::F1;
+
+/// Opens the screen on a gamepad, whose d-pad and face buttons then stand in for the arrow keys,
+/// enter, backspace and escape
+const OPEN_BUTTON: GamepadButtonType = GamepadButtonType::Select;
+
+const SELECTED_COLOR: Color = Color::YELLOW;
+
+/// Lets the local player change the bindings while the game is paused, only there while open
+#[derive(Resource, Default)]
+pub(crate) struct RebindingScreen {
+    /// Index of the selected action in [`Action::ALL`]
+    selected: usize,
+    /// Whether the next button pressed gets bound to the selected action
+    waiting: bool,
+}
+
+#[derive(Component)]
+struct RebindingScreenRoot;
+
+/// One section for the title, one for each action and one for the prompt
+#[derive(Component)]
+struct RebindingText;
+
+fn open_rebinding_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: 10.,
+        color: Color::WHITE,
+    };
+    let sections = std::iter::once(TextSection::new(
+        "Controls\n\n",
+        TextStyle {
+            font_size: 20.,
+            ..style.clone()
+        },
+    ))
+    .chain(
+        Action::ALL
+            .iter()
+            .map(|_| TextSection::new("", style.clone())),
+    )
+    .chain(std::iter::once(TextSection::new("", style.clone())));
+
+    commands.insert_resource(RebindingScreen::default());
+    commands
+        .spawn((
+            RebindingScreenRoot,
+            Name::new("Rebinding screen"),
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    position_type: PositionType::Absolute,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::BLACK.into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Name::new("Rebinding text"),
+                RebindingText,
+                TextBundle::from_sections(sections),
+            ));
+        });
+}
+
+/// Whether the button was just pressed on any gamepad
+fn pad_just_pressed(
+    gamepad_button_input: &Input<GamepadButton>,
+    button_type: GamepadButtonType,
+) -> bool {
+    gamepad_button_input
+        .get_just_pressed()
+        .any(|button| button.button_type == button_type)
+}
+
+fn open_key_just_pressed(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_button_input: Res<Input<GamepadButton>>,
+) -> bool {
+    keyboard_input.just_pressed(OPEN_KEY) || pad_just_pressed(&gamepad_button_input, OPEN_BUTTON)
+}
+
+/// Select actions, wait for buttons to bind them to and close the screen again
+fn update_rebinding_screen(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_button_input: Res<Input<MouseButton>>,
+    gamepad_button_input: Res<Input<GamepadButton>>,
+    mut screen: ResMut<RebindingScreen>,
+    mut bindings: ResMut<Bindings>,
+    root_query: Query<Entity, With<RebindingScreenRoot>>,
+) {
+    let pad_just_pressed = |button_type| pad_just_pressed(&gamepad_button_input, button_type);
+
+    if screen.waiting {
+        if keyboard_input.just_pressed(KeyCode::Escape) || pad_just_pressed(GamepadButtonType::East)
+        {
+            screen.waiting = false;
+            return;
+        }
+
+        let binding = keyboard_input
+            .get_just_pressed()
+            .next()
+            .map(|key| Binding::Key(*key))
+            .or_else(|| {
+                mouse_button_input
+                    .get_just_pressed()
+                    .next()
+                    .map(|button| Binding::Mouse(*button))
+            })
+            .or_else(|| {
+                gamepad_button_input
+                    .get_just_pressed()
+                    .next()
+                    .map(|button| Binding::Gamepad(button.button_type))
+            });
+        if let Some(binding) = binding {
+            bindings.rebind(Action::ALL[screen.selected], binding);
+            bindings.save();
+            screen.waiting = false;
+        }
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Up) || pad_just_pressed(GamepadButtonType::DPadUp) {
+        screen.selected = (screen.selected + Action::ALL.len() - 1) % Action::ALL.len();
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) || pad_just_pressed(GamepadButtonType::DPadDown) {
+        screen.selected = (screen.selected + 1) % Action::ALL.len();
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) || pad_just_pressed(GamepadButtonType::South) {
+        screen.waiting = true;
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) || pad_just_pressed(GamepadButtonType::West) {
+        *bindings = default();
+        bindings.save();
+    }
+    if keyboard_input.any_just_pressed([KeyCode::Escape, OPEN_KEY])
+        || pad_just_pressed(GamepadButtonType::East)
+        || pad_just_pressed(OPEN_BUTTON)
+    {
+        commands.remove_resource::<RebindingScreen>();
+        for entity in &root_query {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn show_bindings(
+    screen: Res<RebindingScreen>,
+    bindings: Res<Bindings>,
+    mut rebinding_text_query: Query<&mut Text, With<RebindingText>>,
+) {
+    if !screen.is_changed() && !bindings.is_changed() {
+        return;
+    }
+    let Ok(mut text) = rebinding_text_query.get_single_mut() else {
+        return;
+    };
+
+    for (index, action) in Action::ALL.into_iter().enumerate() {
+        let bound = bindings
+            .get(action)
+            .iter()
+            .map(Binding::label)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let section = &mut text.sections[index + 1];
+        section.value = format!("{}: {bound}\n", action.label());
+        section.style.color = if index == screen.selected {
+            SELECTED_COLOR
+        } else {
+            Color::WHITE
+        };
+    }
+
+    let prompt = text.sections.last_mut().unwrap();
+    prompt.value = if screen.waiting {
+        format!(
+            "\npress a button for {}, esc/east to cancel",
+            Action::ALL[screen.selected].label()
+        )
+    } else {
+        "\nup/down: select  enter/south: rebind\nbackspace/west: defaults  esc/east: back"
+            .to_string()
+    };
+}
+
+pub(crate) struct RebindingPlugin;
+
+impl Plugin for RebindingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            open_rebinding_screen
+                .run_if(game_paused)
+                .run_if(not(resource_exists::<RebindingScreen>()))
+                .run_if(open_key_just_pressed),
+        )
+        .add_system(
+            update_rebinding_screen
+                .after(open_rebinding_screen)
+                .run_if(resource_exists::<RebindingScreen>()),
+        )
+        .add_system(
+            show_bindings
+                .after(update_rebinding_screen)
+                .run_if(resource_exists::<RebindingScreen>()),
+        );
+    }
+}
@@ -0,0 +1,281 @@
+use bevy::{app::AppExit, prelude::*};
+use rand::Rng;
+
+use crate::{
+    breakout::{
+        snapshot_inputs, spawn_bricks, BreakoutConfig, BreakoutState, PaddleInput, SimulationSet,
+        TickInputs,
+    },
+    classic::ClassicProgress,
+    players::GameMode,
+    rng::GameRng,
+};
+
+/// File the last game is recorded to, next to the game
+const LAST_GAME_PATH: &str = "last-game.replay";
+
+/// Marks a file as a replay
+const MAGIC: &[u8; 4] = b"BKRP";
+
+/// Version of the replay format, bumped whenever it changes
+const VERSION: u8 = 1;
+
+/// A recorded game, with everything needed to play it again tick for tick
+///
+/// The levels aren't part of the recording, so a replay only plays back the same with the level
+/// files it was recorded with.
+#[derive(Clone, Debug)]
+pub(crate) struct Replay {
+    /// Seed of the `GameRng` at the start of the game
+    pub(crate) seed: u64,
+    pub(crate) game_mode: GameMode,
+    /// Whether the game was played by the classic rules
+    pub(crate) classic: bool,
+    /// The config the game started with
+    pub(crate) config: BreakoutConfig,
+    /// The inputs of every tick, starting with the serve that started the game
+    pub(crate) ticks: Vec<Vec<PaddleInput>>,
+}
+
+impl Replay {
+    /// Write the replay in its compact binary format
+    ///
+    /// Runs of ticks with the same inputs, like while the paddles stand still, are stored once.
+    pub(crate) fn encode(&self) -> Result<Vec<u8>, String> {
+        let config = ron::to_string(&self.config).map_err(|error| error.to_string())?;
+        let game_mode = GameMode::ALL
+            .iter()
+            .position(|mode| *mode == self.game_mode)
+            .unwrap_or_default();
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.extend(self.seed.to_le_bytes());
+        bytes.push(game_mode as u8);
+        bytes.push(self.classic as u8);
+        write_varint(&mut bytes, config.len() as u64);
+        bytes.extend(config.as_bytes());
+
+        write_varint(&mut bytes, self.ticks.len() as u64);
+        let mut ticks = self.ticks.iter().peekable();
+        while let Some(inputs) = ticks.next() {
+            let mut run_length = 1;
+            while ticks.next_if(|next| same_inputs(inputs, next)).is_some() {
+                run_length += 1;
+            }
+            write_varint(&mut bytes, run_length);
+            write_varint(&mut bytes, inputs.len() as u64);
+            for input in inputs {
+                bytes.extend(input.move_direction.to_bits().to_le_bytes());
+                bytes.push(input.serve as u8);
+            }
+        }
+        Ok(bytes)
+    }
+
+    pub(crate) fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader { bytes };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err("not a replay".to_string());
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(format!("unsupported replay version {version}"));
+        }
+
+        let seed = u64::from_le_bytes(reader.array()?);
+        let game_mode = reader.u8()?;
+        let game_mode = *GameMode::ALL
+            .get(game_mode as usize)
+            .ok_or_else(|| format!("unknown game mode {game_mode}"))?;
+        let classic = reader.u8()? != 0;
+        let config_length = reader.length()?;
+        let config = ron::de::from_bytes(reader.take(config_length)?)
+            .map_err(|error| format!("invalid config: {error}"))?;
+
+        let tick_count = reader.length()?;
+        let mut ticks = Vec::new();
+        while ticks.len() < tick_count {
+            let run_length = reader.length()?;
+            let input_count = reader.length()?;
+            let inputs = (0..input_count)
+                .map(|_| {
+                    Ok(PaddleInput {
+                        move_direction: f32::from_bits(u32::from_le_bytes(reader.array()?)),
+                        serve: reader.u8()? != 0,
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            if ticks.len() + run_length > tick_count {
+                return Err("more ticks than the replay says it has".to_string());
+            }
+            ticks.extend(std::iter::repeat(inputs).take(run_length));
+        }
+
+        Ok(Self {
+            seed,
+            game_mode,
+            classic,
+            config,
+            ticks,
+        })
+    }
+
+    pub(crate) fn load(path: &str) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|error| error.to_string())?;
+        Self::decode(&bytes)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save(&self, path: &str) {
+        let result = self
+            .encode()
+            .and_then(|bytes| std::fs::write(path, bytes).map_err(|error| error.to_string()));
+        match result {
+            Ok(()) => info!("Recorded the game to {path}"),
+            Err(error) => warn!("Could not record the game to {path}: {error}"),
+        }
+    }
+
+    /// Browsers have no file to keep the recording in
+    #[cfg(target_arch = "wasm32")]
+    fn save(&self, _path: &str) {}
+}
+
+/// Whether two ticks have exactly the same inputs, down to the sign of a zero
+fn same_inputs(inputs: &[PaddleInput], other_inputs: &[PaddleInput]) -> bool {
+    inputs.len() == other_inputs.len()
+        && inputs.iter().zip(other_inputs).all(|(input, other)| {
+            input.move_direction.to_bits() == other.move_direction.to_bits()
+                && input.serve == other.serve
+        })
+}
+
+/// Append an unsigned LEB128 number, using a byte for every seven bits
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Reads a replay from the front, failing on a truncated file
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < count {
+            return Err("the replay is cut off".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("invalid number in the replay".to_string())
+    }
+
+    fn length(&mut self) -> Result<usize, String> {
+        usize::try_from(self.varint()?).map_err(|error| error.to_string())
+    }
+}
+
+/// The game being recorded
+#[derive(Resource, Deref, DerefMut)]
+pub(crate) struct Recording(pub(crate) Replay);
+
+/// Start recording at the serve that starts a game, with a fresh seed for the random number
+/// generator so the game can be played again
+fn start_recording(
+    mut commands: Commands,
+    config: Res<BreakoutConfig>,
+    game_mode: Res<GameMode>,
+    classic_progress: Option<Res<ClassicProgress>>,
+    tick_inputs: Res<TickInputs>,
+    mut rng: ResMut<GameRng>,
+) {
+    let seed = rng.gen();
+    *rng = GameRng::seeded(seed);
+    commands.insert_resource(Recording(Replay {
+        seed,
+        game_mode: *game_mode,
+        // Only the classic rules keep track of classic progress
+        classic: classic_progress.is_some(),
+        config: config.clone(),
+        ticks: vec![tick_inputs.0.clone()],
+    }));
+}
+
+fn record_inputs(tick_inputs: Res<TickInputs>, mut recording: ResMut<Recording>) {
+    recording.ticks.push(tick_inputs.0.clone());
+}
+
+/// Write the recording to disk once the game is over, or the game is closed halfway
+fn save_recording(mut commands: Commands, recording: Res<Recording>) {
+    recording.save(LAST_GAME_PATH);
+    commands.remove_resource::<Recording>();
+}
+
+/// Record every game to a replay of the last game played
+pub(crate) struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(RecordingPlugin)
+            .add_system(
+                save_recording
+                    .run_if(resource_exists::<Recording>())
+                    .in_schedule(OnEnter(BreakoutState::Finished)),
+            )
+            .add_system(
+                save_recording
+                    .run_if(resource_exists::<Recording>())
+                    .run_if(on_event::<AppExit>())
+                    .in_base_set(CoreSet::Last),
+            );
+    }
+}
+
+/// Record every game played into the [`Recording`], without saving it anywhere
+pub(crate) struct RecordingPlugin;
+
+impl Plugin for RecordingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            start_recording
+                .before(spawn_bricks)
+                .in_schedule(OnExit(BreakoutState::Start)),
+        )
+        .add_system(
+            record_inputs
+                .after(snapshot_inputs)
+                .in_set(SimulationSet::Input)
+                .run_if(resource_exists::<Recording>())
+                .in_schedule(CoreSchedule::FixedUpdate),
+        );
+    }
+}
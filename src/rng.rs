@@ -0,0 +1,22 @@
+use bevy::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// The random number generator of the simulation
+///
+/// Everything random that affects the game draws from this generator, in simulation order.
+#[derive(Resource, Deref, DerefMut)]
+pub(crate) struct GameRng(pub(crate) ChaCha8Rng);
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self(ChaCha8Rng::from_entropy())
+    }
+}
+
+impl GameRng {
+    /// A generator that always draws the same numbers, to play a recorded game again
+    pub(crate) fn seeded(seed: u64) -> Self {
+        Self(ChaCha8Rng::seed_from_u64(seed))
+    }
+}
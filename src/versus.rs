@@ -0,0 +1,281 @@
+use bevy::prelude::*;
+use rand::seq::index::sample;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    breakout::{
+        explode_bricks, spawn_brick, spawn_held_ball, Ball, BottomCollisionEvent, BreakoutConfig,
+        BreakoutState, Brick, BrickDestroyedEvent, Court, GameResult, Paddle, Position,
+        ServingPaddle, SimulationSet,
+    },
+    collision::Collider,
+    level::BrickKind,
+    players::{versus, CourtPlayers},
+    rng::GameRng,
+};
+
+/// Color of the bricks sent over by the opponent
+const GARBAGE_COLOR: Color = Color::GRAY;
+
+/// Tunable parameters of versus
+#[derive(Reflect, FromReflect, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub(crate) struct VersusConfig {
+    /// Number of cells a row of garbage bricks is divided into
+    pub(crate) garbage_columns: usize,
+    /// Number of cells left empty in every row of garbage, picked at random
+    pub(crate) garbage_holes: usize,
+    pub(crate) garbage_points: u32,
+    /// Distance from the bottom of the court the wall never grows past, once it would the balls
+    /// of the opponent are sped up instead
+    pub(crate) garbage_floor: f32,
+    /// How much faster the balls of the opponent travel after every speed-up
+    pub(crate) speed_up_scale: f32,
+    /// The speed-ups never make the balls faster than this
+    pub(crate) max_ball_speed_scale: f32,
+}
+
+impl Default for VersusConfig {
+    fn default() -> Self {
+        Self {
+            garbage_columns: 10,
+            garbage_holes: 3,
+            garbage_points: 1,
+            garbage_floor: 150.,
+            speed_up_scale: 1.15,
+            max_ball_speed_scale: 2.,
+        }
+    }
+}
+
+/// Index of the court of the opponent of the player in a court
+fn opponent(court: usize, court_count: usize) -> usize {
+    (court + 1) % court_count.max(1)
+}
+
+/// Take balls that left a court out of play, and lose a life when the last ball of that court is
+/// gone
+///
+/// Play goes on in the other court while a new ball waits to be served, until one of the players
+/// runs out of lives and loses.
+fn versus_lives(
+    mut commands: Commands,
+    config: Res<BreakoutConfig>,
+    serving_paddle: Res<ServingPaddle>,
+    mut court_players: CourtPlayers,
+    mut bottom_collision_events: EventReader<BottomCollisionEvent>,
+    court_query: Query<(Entity, &Court)>,
+    ball_query: Query<(Entity, &Parent), With<Ball>>,
+    paddle_query: Query<(Entity, &Paddle, &Position, &Parent)>,
+    mut next_state: ResMut<NextState<BreakoutState>>,
+) {
+    let mut lost_balls = Vec::new();
+    for BottomCollisionEvent { ball_entity } in bottom_collision_events.iter() {
+        if !lost_balls.contains(ball_entity) {
+            commands.entity(*ball_entity).despawn_recursive();
+            lost_balls.push(*ball_entity);
+        }
+    }
+
+    let court_count = court_query.iter().len();
+    for (court_entity, court) in &court_query {
+        let in_court = |(ball_entity, parent): &(Entity, &Parent)| {
+            parent.get() == court_entity && !lost_balls.contains(ball_entity)
+        };
+        let lost_here = ball_query
+            .iter_many(&lost_balls)
+            .any(|(_, parent)| parent.get() == court_entity);
+        if !lost_here || ball_query.iter().any(|ball| in_court(&ball)) {
+            continue;
+        }
+
+        let lives = court_players.lives_mut(court.index);
+        *lives = lives.saturating_sub(1);
+        if *lives == 0 {
+            commands.insert_resource(GameResult::PlayerWon(opponent(court.index, court_count)));
+            next_state.set(BreakoutState::Finished);
+        } else {
+            spawn_held_ball(
+                &mut commands,
+                &config,
+                &serving_paddle,
+                court_entity,
+                &paddle_query,
+            );
+        }
+    }
+}
+
+/// The bottom of the wall of bricks in a court, and the row below it
+struct Wall {
+    court: Entity,
+    bottom: f32,
+    next_row: usize,
+}
+
+/// Send the opponent a row of garbage bricks for every row of bricks a player clears, or speed up
+/// the balls of the opponent once their wall can't grow any further
+fn send_garbage(
+    mut commands: Commands,
+    config: Res<BreakoutConfig>,
+    mut rng: ResMut<GameRng>,
+    mut brick_destroyed_events: EventReader<BrickDestroyedEvent>,
+    mut court_query: Query<(Entity, &mut Court)>,
+    brick_query: Query<(&Brick, &Transform, &Collider, &Parent)>,
+) {
+    let mut cleared_rows: Vec<(Entity, usize)> = Vec::new();
+    for destroyed in brick_destroyed_events.iter() {
+        let row = (destroyed.court, destroyed.row);
+        if cleared_rows.contains(&row) {
+            continue;
+        }
+        let bricks_left = brick_query.iter().any(|(brick, .., parent)| {
+            parent.get() == destroyed.court
+                && brick.row == destroyed.row
+                && brick.kind != BrickKind::Unbreakable
+        });
+        if !bricks_left {
+            cleared_rows.push(row);
+        }
+    }
+
+    let versus = &config.versus;
+    let court_count = court_query.iter().len();
+    // Garbage sent this tick only shows up in the query next tick, so keep track of it here
+    let mut walls: Vec<Wall> = Vec::new();
+    for (court_entity, _) in cleared_rows {
+        let Ok((_, court)) = court_query.get(court_entity) else {
+            continue;
+        };
+        let opponent_index = opponent(court.index, court_count);
+        let Some((opponent_entity, _)) = court_query
+            .iter()
+            .find(|(_, court)| court.index == opponent_index)
+        else {
+            continue;
+        };
+
+        let wall = match walls.iter_mut().find(|wall| wall.court == opponent_entity) {
+            Some(wall) => wall,
+            None => {
+                let opponent_bricks = || {
+                    brick_query
+                        .iter()
+                        .filter(|(.., parent)| parent.get() == opponent_entity)
+                };
+                walls.push(Wall {
+                    court: opponent_entity,
+                    bottom: opponent_bricks()
+                        .map(|(_, transform, collider, _)| {
+                            transform.translation.y - collider.get_half_size().y
+                        })
+                        .reduce(f32::min)
+                        .unwrap_or(config.court_size[1] / 2. - config.bricks_top_offset),
+                    next_row: opponent_bricks()
+                        .map(|(brick, ..)| brick.row + 1)
+                        .max()
+                        .unwrap_or_default(),
+                });
+                walls.last_mut().unwrap()
+            }
+        };
+
+        let size = Vec2::new(
+            config.court_size[0] / versus.garbage_columns.max(1) as f32,
+            config.brick_height,
+        ) - config.brick_padding / 2.;
+        let y = wall.bottom - config.brick_padding / 2. - size.y / 2.;
+        if y - size.y / 2. < -config.court_size[1] / 2. + versus.garbage_floor {
+            let (_, mut opponent_court) = court_query.get_mut(opponent_entity).unwrap();
+            opponent_court.ball_speed_scale = (opponent_court.ball_speed_scale
+                * versus.speed_up_scale)
+                .min(versus.max_ball_speed_scale);
+            continue;
+        }
+
+        spawn_garbage_row(
+            &mut commands,
+            &config,
+            &mut rng,
+            opponent_entity,
+            wall.next_row,
+            y,
+        );
+        wall.bottom = y - size.y / 2.;
+        wall.next_row += 1;
+    }
+}
+
+/// Spawn a row of garbage bricks across a court, with a few holes in it
+fn spawn_garbage_row(
+    commands: &mut Commands,
+    config: &BreakoutConfig,
+    rng: &mut GameRng,
+    court: Entity,
+    row: usize,
+    y: f32,
+) {
+    let versus = &config.versus;
+    let columns = versus.garbage_columns.max(1);
+    let holes = sample(&mut rng.0, columns, versus.garbage_holes.min(columns - 1)).into_vec();
+    let cell_width = config.court_size[0] / columns as f32;
+    let size = Vec2::new(cell_width, config.brick_height) - config.brick_padding / 2.;
+
+    commands.entity(court).with_children(|parent| {
+        for column in (0..columns).filter(|column| !holes.contains(column)) {
+            let brick = Brick {
+                points: versus.garbage_points,
+                row,
+                kind: BrickKind::Normal,
+                hits_left: 1,
+            };
+            let x = -config.court_size[0] / 2. + (column as f32 + 0.5) * cell_width;
+            spawn_brick(parent, brick, GARBAGE_COLOR, Vec2::new(x, y), size);
+        }
+    });
+}
+
+/// A player who clears their wall wins
+fn win_by_clearing_wall(
+    mut commands: Commands,
+    court_query: Query<(Entity, &Court)>,
+    brick_query: Query<(&Brick, &Parent)>,
+    mut next_state: ResMut<NextState<BreakoutState>>,
+) {
+    for (court_entity, court) in &court_query {
+        let cleared = brick_query
+            .iter()
+            .filter(|(_, parent)| parent.get() == court_entity)
+            .all(|(brick, _)| brick.kind == BrickKind::Unbreakable);
+        if cleared {
+            commands.insert_resource(GameResult::PlayerWon(court.index));
+            next_state.set(BreakoutState::Finished);
+            return;
+        }
+    }
+}
+
+/// The rules of versus: rows cleared go over to the opponent, and the game ends when a player
+/// runs out of lives or clears their wall
+pub(crate) struct VersusPlugin;
+
+impl Plugin for VersusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            (
+                versus_lives
+                    .run_if(in_state(BreakoutState::Playing))
+                    .run_if(on_event::<BottomCollisionEvent>()),
+                send_garbage
+                    .run_if(in_state(BreakoutState::Playing))
+                    .run_if(on_event::<BrickDestroyedEvent>()),
+                win_by_clearing_wall.run_if(in_state(BreakoutState::Playing)),
+            )
+                .chain()
+                .distributive_run_if(versus)
+                .after(explode_bricks)
+                .in_set(SimulationSet::Rules)
+                .in_schedule(CoreSchedule::FixedUpdate),
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Episodes of the learning environment, checking its observations and rewards follow the game
+
+use breakout::{BreakoutState, Environment, EnvironmentConfig, PaddleInput};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Tick rate the episodes are played at, the one browsers run at
+const TICK_RATE: f32 = 120.;
+
+/// Longest an episode is played, in steps
+const MAX_STEPS: u64 = 2000;
+
+fn environment(life_reward: f32) -> Environment {
+    Environment::new(EnvironmentConfig {
+        tick_rate: Some(TICK_RATE),
+        life_reward,
+        max_steps: Some(MAX_STEPS),
+        ..Default::default()
+    })
+}
+
+/// An agent that follows the ball with a random offset, so it sometimes scores and sometimes
+/// misses
+fn random_action(rng: &mut ChaCha8Rng, ball_x: f32, paddle_x: f32) -> PaddleInput {
+    let target = ball_x + rng.gen_range(-40.0..=40.);
+    PaddleInput {
+        move_direction: ((target - paddle_x) / 10.).clamp(-1., 1.),
+        serve: false,
+    }
+}
+
+#[test]
+fn rewards_add_up_to_score_and_lives() {
+    let life_reward = 25.;
+    let mut environment = environment(life_reward);
+    let mut rng = ChaCha8Rng::seed_from_u64(1);
+    let mut observation = environment.reset(1);
+    let simulation = environment.simulation().unwrap();
+    let (start_score, start_lives) = (simulation.score(), simulation.lives());
+
+    let mut total_reward = 0.;
+    let mut done = false;
+    while !done {
+        let action = random_action(
+            &mut rng,
+            observation.ball_position.x,
+            observation.paddle_position.x,
+        );
+        let reward;
+        (observation, reward, done) = environment.step(action);
+        total_reward += reward;
+    }
+
+    let simulation = environment.simulation().unwrap();
+    let expected = simulation.score() as f32 - start_score as f32
+        + (simulation.lives() as f32 - start_lives as f32) * life_reward;
+    assert_eq!(total_reward, expected);
+    assert!(simulation.score() > start_score, "the agent never scored");
+}
+
+#[test]
+fn episodes_end_when_the_game_is_over() {
+    let mut environment = environment(10.);
+    environment.reset(2);
+
+    // Waiting in a corner drops every ball
+    let action = PaddleInput {
+        move_direction: -1.,
+        serve: false,
+    };
+    let mut steps = 0;
+    let mut lost_lives = false;
+    loop {
+        let (_, reward, done) = environment.step(action.clone());
+        steps += 1;
+        lost_lives |= reward < 0.;
+        if done {
+            break;
+        }
+    }
+
+    let simulation = environment.simulation().unwrap();
+    assert!(steps < MAX_STEPS, "the episode ran out of steps");
+    assert_eq!(simulation.state(), BreakoutState::Finished);
+    assert_eq!(simulation.lives(), 0);
+    assert!(lost_lives, "losing lives wasn't penalized");
+}
+
+#[test]
+fn observations_show_the_game() {
+    let mut environment = environment(10.);
+    let observation = environment.reset(3);
+
+    assert_eq!(
+        observation.bricks.len(),
+        observation.rows * observation.columns
+    );
+    assert!(observation.rows > 0 && observation.columns > 0);
+    assert!(observation.ball_held, "the first ball waits to be served");
+    assert!(observation
+        .bricks
+        .iter()
+        .all(|value| (-1.0..=1.).contains(value)));
+    let bricks_seen = observation
+        .bricks
+        .iter()
+        .filter(|value| **value > 0.)
+        .count();
+    let breakable_bricks = environment
+        .simulation()
+        .unwrap()
+        .bricks()
+        .into_iter()
+        .filter(|brick| brick.breakable)
+        .count();
+    assert!(bricks_seen >= breakable_bricks);
+
+    let values = observation.to_vec();
+    assert_eq!(values.len(), 7 + observation.bricks.len());
+    assert!(values[..7].iter().all(|value| value.abs() <= 1.));
+
+    // Serving is left to the environment, so the ball is on its way after a step
+    let (observation, _, _) = environment.step(PaddleInput::default());
+    assert!(!observation.ball_held);
+    assert_ne!(observation.ball_velocity.y, 0.);
+}
+
+#[test]
+fn same_seed_and_actions_play_the_same_episode() {
+    let play = || {
+        let mut environment = environment(10.);
+        let mut rng = ChaCha8Rng::seed_from_u64(4);
+        let mut observation = environment.reset(4);
+        let mut rewards = Vec::new();
+        for _ in 0..300 {
+            let action = random_action(
+                &mut rng,
+                observation.ball_position.x,
+                observation.paddle_position.x,
+            );
+            let (next, reward, done) = environment.step(action);
+            observation = next;
+            rewards.push(reward);
+            if done {
+                break;
+            }
+        }
+        (observation, rewards)
+    };
+    assert_eq!(play(), play());
+}
@@ -0,0 +1,495 @@
+//! Randomized rallies against the headless simulation, checking the rules of the game hold on
+//! every tick
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::math::Vec2;
+use breakout::{
+    AimingBot, BallState, BreakoutState, BrickState, GameMode, HumanTracker, PaddleInput,
+    PerfectTracker, Simulation,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Tick rate of most rallies, the one browsers run at, so the tests cover minutes of play
+const TICK_RATE: f32 = 120.;
+
+/// Longest a single rally is played before moving on to the next, in seconds
+const MAX_SECONDS: f32 = 120.;
+
+/// Longest the rally at the tick rate of the desktop is played, which is much slower to simulate
+const MAX_DESKTOP_SECONDS: f32 = 10.;
+
+/// How far a ball may stick out of the court due to floating point error
+const EPSILON: f32 = 0.01;
+
+/// Longest the bots play each of their games, in seconds
+const MAX_BOT_SECONDS: f32 = 60.;
+
+/// End of the range of seeds of the simulations shooting balls at bricks
+const BOUNCE_SIMULATIONS: u64 = 403;
+
+/// Ticks a ball shot at a brick gets to bounce off it, and then to move away
+const BOUNCE_TICKS: u32 = 15;
+
+/// How far a ball that bounced off a brick is put back inside of it, like rounding error does
+const BOUNCE_OVERLAP: f32 = 1e-5;
+
+/// Fastest a ball may travel relative to its velocity, like when it speeds up in versus
+const MAX_BALL_SPEED_SCALE: f32 = 2.;
+
+/// Ticks of the game that is recorded and played back
+const REPLAY_TICKS: u64 = 2000;
+
+/// A sloppy player that follows the ball with a changing offset, and sometimes looks away
+struct RandomPlayer {
+    rng: ChaCha8Rng,
+    /// Chance the player looks away whenever they change their mind
+    distraction: f64,
+    /// Offset from the ball the paddle aims for
+    offset: f32,
+    /// Whether the player ignores the ball, and moves the paddle randomly
+    distracted: bool,
+    /// Direction the paddle moves in while distracted
+    drift: f32,
+    /// Ticks until the player changes their mind
+    ticks_left: u32,
+}
+
+impl RandomPlayer {
+    fn new(seed: u64) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        Self {
+            distraction: rng.gen_range(0.0..0.2),
+            rng,
+            offset: 0.,
+            distracted: false,
+            drift: 0.,
+            ticks_left: 0,
+        }
+    }
+
+    fn input(&mut self, simulation: &Simulation) -> PaddleInput {
+        if self.ticks_left == 0 {
+            self.ticks_left = (self.rng.gen_range(0.1..1.) / simulation.tick_duration()) as u32;
+            self.distracted = self.rng.gen_bool(self.distraction);
+            self.drift = self.rng.gen_range(-1.0..=1.);
+            self.offset = self.rng.gen_range(-1.0..=1.);
+        }
+        self.ticks_left -= 1;
+
+        let serve = self.rng.gen_bool(0.1);
+        let Some(paddle) = simulation
+            .paddles()
+            .into_iter()
+            .find(|paddle| paddle.input == 0)
+        else {
+            return PaddleInput::default();
+        };
+        let ball = simulation
+            .balls()
+            .into_iter()
+            .filter(|ball| ball.court == paddle.court)
+            .min_by(|a, b| a.position.y.total_cmp(&b.position.y));
+        let move_direction = match ball {
+            Some(ball) if !self.distracted => {
+                let target = ball.position.x + self.offset * paddle.size.x / 2.;
+                ((target - paddle.position.x) / 10.).clamp(-1., 1.)
+            }
+            _ => self.drift,
+        };
+
+        PaddleInput {
+            move_direction,
+            serve,
+        }
+    }
+}
+
+/// Everything seen on the previous tick, to check the next tick against
+struct Checker {
+    state: BreakoutState,
+    lives: u32,
+    balls: HashMap<u64, BallState>,
+    /// Whether serve was held on each input
+    serves: Vec<bool>,
+    /// Every brick destroyed so far this game
+    destroyed_bricks: HashSet<u64>,
+    /// Points scored by the bricks that were destroyed
+    destroyed_points: u32,
+}
+
+impl Checker {
+    fn new(simulation: &Simulation) -> Self {
+        Self {
+            state: simulation.state(),
+            lives: simulation.lives(),
+            balls: ball_map(simulation),
+            serves: Vec::new(),
+            destroyed_bricks: HashSet::new(),
+            destroyed_points: 0,
+        }
+    }
+
+    fn check(&mut self, simulation: &Simulation) {
+        let serves: Vec<bool> = simulation
+            .inputs()
+            .iter()
+            .map(|input| input.serve)
+            .collect();
+        let serve_pressed = serves
+            .iter()
+            .enumerate()
+            .any(|(index, serve)| *serve && !self.serves.get(index).map_or(false, |serve| *serve));
+        let tick = simulation.tick();
+        let state = simulation.state();
+        let lives = simulation.lives();
+        let court_size = simulation.court_size();
+        let half_ball = simulation.ball_size() / 2.;
+        let bricks = simulation.bricks();
+
+        // The ball stays inside the court, except for leaving through the bottom
+        let bound = court_size / 2. - half_ball + EPSILON;
+        let balls = ball_map(simulation);
+        for ball in balls.values() {
+            assert!(
+                ball.position.x.abs() <= bound.x && ball.position.y.abs() <= bound.y,
+                "tick {tick}: ball left the court at {}",
+                ball.position
+            );
+        }
+
+        let bricks_cleared = !bricks.iter().any(|brick| brick.breakable);
+        let left_with_bricks = self.state == BreakoutState::Playing
+            && matches!(state, BreakoutState::LevelCleared | BreakoutState::Finished)
+            && bricks_cleared;
+        if !left_with_bricks {
+            let tick_duration = simulation.tick_duration() * MAX_BALL_SPEED_SCALE;
+            for (id, ball) in &self.balls {
+                if balls.contains_key(id) {
+                    continue;
+                }
+                let next_y = ball.position.y + ball.velocity.y.min(0.) * tick_duration;
+                assert!(
+                    next_y - half_ball <= -court_size.y / 2. + EPSILON,
+                    "tick {tick}: ball vanished at {} moving {}",
+                    ball.position,
+                    ball.velocity
+                );
+            }
+        }
+
+        // A brick is destroyed once, and never scored again
+        for destroyed in simulation.destroyed_bricks() {
+            assert!(
+                self.destroyed_bricks.insert(destroyed.id),
+                "tick {tick}: brick {} was destroyed twice",
+                destroyed.id
+            );
+            self.destroyed_points += destroyed.points * destroyed.hits;
+        }
+        for brick in &bricks {
+            assert!(
+                !self.destroyed_bricks.contains(&brick.id),
+                "tick {tick}: destroyed brick {} is still standing",
+                brick.id
+            );
+        }
+
+        // Every hit on a breakable brick scores its points, so the score adds up from the bricks
+        let damage_points: u32 = bricks
+            .iter()
+            .filter(|brick| brick.breakable)
+            .map(|brick| brick.points * (brick.hits - brick.hits_left))
+            .sum();
+        assert_eq!(
+            simulation.score(),
+            self.destroyed_points + damage_points,
+            "tick {tick}: the score doesn't add up to the bricks hit"
+        );
+
+        // Lives are only lost by dropping the last ball, which ends the rally, and only gained by
+        // catching an extra life
+        let lost_life = lives + 1 == self.lives;
+        let same_lives = lives == self.lives;
+        if state == BreakoutState::Playing {
+            assert!(
+                same_lives || lives == self.lives + 1,
+                "tick {tick}: lives went from {} to {lives} during a rally",
+                self.lives
+            );
+        }
+
+        let valid = match (&self.state, &state) {
+            (BreakoutState::Playing, BreakoutState::Playing) => true,
+            (previous, next) if previous == next => same_lives,
+            (BreakoutState::Start, BreakoutState::Serve) => same_lives,
+            (BreakoutState::Serve, BreakoutState::Playing) => serve_pressed && same_lives,
+            (BreakoutState::Playing, BreakoutState::Serve) => lost_life && lives > 0,
+            (BreakoutState::Playing, BreakoutState::LevelCleared) => bricks_cleared && same_lives,
+            (BreakoutState::Playing, BreakoutState::Finished) => {
+                (lost_life && lives == 0) || (bricks_cleared && same_lives)
+            }
+            (BreakoutState::LevelCleared, BreakoutState::Serve) => same_lives,
+            _ => false,
+        };
+        assert!(
+            valid,
+            "tick {tick}: invalid transition from {:?} to {state:?} with {lives} lives",
+            self.state
+        );
+
+        self.state = state;
+        self.lives = lives;
+        self.balls = balls;
+        self.serves = serves;
+    }
+}
+
+fn ball_map(simulation: &Simulation) -> HashMap<u64, BallState> {
+    simulation
+        .balls()
+        .into_iter()
+        .map(|ball| (ball.id, ball))
+        .collect()
+}
+
+/// Play until the game is over or out of time, checking every tick
+fn play(
+    simulation: &mut Simulation,
+    max_seconds: f32,
+    mut inputs: impl FnMut(&Simulation) -> Vec<PaddleInput>,
+) {
+    let mut checker = Checker::new(simulation);
+    let max_ticks = simulation.tick() + (max_seconds / simulation.tick_duration()) as u64;
+    while simulation.tick() < max_ticks && simulation.state() != BreakoutState::Finished {
+        let inputs = inputs(simulation);
+        simulation.step(&inputs);
+        checker.check(simulation);
+    }
+}
+
+/// Play a rally of a single player game with a random player
+fn play_rally(mut simulation: Simulation, seed: u64, tick_rate: f32, max_seconds: f32) {
+    simulation.set_tick_rate(tick_rate);
+    simulation.start(GameMode::SinglePlayer);
+    assert_eq!(simulation.state(), BreakoutState::Serve);
+
+    let mut player = RandomPlayer::new(seed);
+    play(&mut simulation, max_seconds, |simulation| {
+        vec![player.input(simulation)]
+    });
+}
+
+#[test]
+fn rallies_keep_the_rules() {
+    for seed in 0..4 {
+        play_rally(Simulation::new(seed), seed, TICK_RATE, MAX_SECONDS);
+    }
+}
+
+#[test]
+fn classic_rallies_keep_the_rules() {
+    for seed in 100..103 {
+        play_rally(Simulation::classic(seed), seed, TICK_RATE, MAX_SECONDS);
+    }
+}
+
+#[test]
+fn desktop_rally_keeps_the_rules() {
+    play_rally(Simulation::new(200), 200, 1000., MAX_DESKTOP_SECONDS);
+}
+
+#[test]
+fn bots_keep_the_rules() {
+    let mut simulation = Simulation::new(300);
+    simulation.set_tick_rate(TICK_RATE);
+    simulation.add_bot(0, PerfectTracker::default());
+    simulation.start(GameMode::SinglePlayer);
+    play(&mut simulation, MAX_BOT_SECONDS, |_| Vec::new());
+
+    let mut simulation = Simulation::new(301);
+    simulation.set_tick_rate(TICK_RATE);
+    simulation.add_bot(0, AimingBot);
+    simulation.add_bot(1, HumanTracker::new(301));
+    simulation.start(GameMode::CoOpSideBySide);
+    play(&mut simulation, MAX_BOT_SECONDS, |_| Vec::new());
+}
+
+#[test]
+fn serve_held_on_entering_start_does_not_pick_a_game_mode() {
+    let mut simulation = Simulation::new(8);
+    let serve = [PaddleInput {
+        serve: true,
+        ..Default::default()
+    }];
+
+    // Like the press that restarted the game, still held on the first ticks of the next one
+    simulation.step(&serve);
+    simulation.step(&serve);
+    assert_eq!(simulation.state(), BreakoutState::Start);
+
+    simulation.step(&[]);
+    simulation.step(&serve);
+    assert_eq!(simulation.state(), BreakoutState::Serve);
+}
+
+#[test]
+fn one_bounce_is_one_hit() {
+    let mut rng = ChaCha8Rng::seed_from_u64(400);
+    for seed in 400..BOUNCE_SIMULATIONS {
+        let mut simulation = Simulation::new(seed);
+        simulation.set_tick_rate(TICK_RATE);
+        // The pillars, with a row of bricks that take two hits
+        simulation.set_level(2);
+        simulation.start(GameMode::SinglePlayer);
+        simulation.step(&[]);
+        simulation.step(&[PaddleInput {
+            serve: true,
+            ..Default::default()
+        }]);
+        assert_eq!(simulation.state(), BreakoutState::Playing);
+
+        let tough_bricks: Vec<BrickState> = simulation
+            .bricks()
+            .into_iter()
+            .filter(|brick| brick.breakable && brick.hits == 2)
+            .collect();
+        assert!(!tough_bricks.is_empty());
+        let half_ball = simulation.ball_size() / 2.;
+
+        // Shoot the ball up into the middle of every brick once at a random angle, so it hits no
+        // other brick
+        for brick in tough_bricks {
+            let ball = &simulation.balls()[0];
+            let speed = ball.velocity.length();
+            let angle = rng.gen_range(-0.3..0.3f32);
+            let velocity = Vec2::new(angle.sin(), angle.cos()) * speed;
+            let contact = Vec2::new(
+                brick.position.x + rng.gen_range(-0.1..0.1) * brick.size.x,
+                brick.position.y - brick.size.y / 2. - half_ball,
+            );
+            let position = contact - velocity.normalize() * rng.gen_range(0.1..10.);
+            simulation.place_ball(ball.id, position, velocity);
+
+            let score = simulation.score();
+            let mut ticks = 0;
+            while simulation.balls()[0].velocity.y > 0. {
+                assert!(
+                    ticks < BOUNCE_TICKS,
+                    "seed {seed}: the ball didn't bounce off brick {}",
+                    brick.id
+                );
+                simulation.step(&[]);
+                ticks += 1;
+            }
+
+            // Rounding can leave the ball a hair inside the brick after bouncing off it
+            let ball = &simulation.balls()[0];
+            let position = Vec2::new(ball.position.x, contact.y + BOUNCE_OVERLAP);
+            simulation.place_ball(ball.id, position, ball.velocity);
+            for _ in 0..BOUNCE_TICKS {
+                simulation.step(&[]);
+            }
+
+            let hits_left = simulation
+                .bricks()
+                .into_iter()
+                .find(|standing| standing.id == brick.id)
+                .map(|standing| standing.hits_left);
+            assert_eq!(
+                hits_left,
+                Some(brick.hits - 1),
+                "seed {seed}: brick {} didn't take exactly one hit",
+                brick.id
+            );
+            // The brick scores its points on every hit, so no other brick was hit either
+            assert_eq!(
+                simulation.score() - score,
+                brick.points,
+                "seed {seed}: brick {} took more than one hit from a single bounce",
+                brick.id
+            );
+        }
+    }
+}
+
+/// What a game played back has to match of the recorded one, leaving out the ids, which differ
+/// between the two
+#[derive(Debug, PartialEq)]
+struct Snapshot {
+    state: BreakoutState,
+    score: u32,
+    lives: u32,
+    /// Position and velocity of every ball, from left to right
+    balls: Vec<(Vec2, Vec2)>,
+    /// Position and hits left of every brick, from left to right
+    bricks: Vec<(Vec2, u32)>,
+}
+
+impl Snapshot {
+    fn new(simulation: &Simulation) -> Self {
+        let mut balls: Vec<_> = simulation
+            .balls()
+            .into_iter()
+            .map(|ball| (ball.position, ball.velocity))
+            .collect();
+        balls.sort_by(|a, b| a.0.to_array().partial_cmp(&b.0.to_array()).unwrap());
+        let mut bricks: Vec<_> = simulation
+            .bricks()
+            .into_iter()
+            .map(|brick| (brick.position, brick.hits_left))
+            .collect();
+        bricks.sort_by(|a, b| a.0.to_array().partial_cmp(&b.0.to_array()).unwrap());
+        Self {
+            state: simulation.state(),
+            score: simulation.score(),
+            lives: simulation.lives(),
+            balls,
+            bricks,
+        }
+    }
+}
+
+#[test]
+fn seeking_back_in_a_replay_plays_the_same_game() {
+    let mut simulation = Simulation::new(9);
+    simulation.set_tick_rate(TICK_RATE);
+    simulation.record();
+    simulation.start(GameMode::SinglePlayer);
+    let mut player = RandomPlayer::new(9);
+    simulation.step_n(REPLAY_TICKS, |simulation| vec![player.input(simulation)]);
+    assert_ne!(simulation.state(), BreakoutState::Start);
+    let replay = simulation.recording().expect("the game wasn't recorded");
+    let recorded = Snapshot::new(&simulation);
+
+    // The recording starts with the serve that started the game
+    let mut playback = Simulation::play_back(&replay).unwrap();
+    playback.step_n(REPLAY_TICKS + 1, |_| Vec::new());
+    assert_eq!(Snapshot::new(&playback), recorded);
+
+    playback.seek(REPLAY_TICKS / 2);
+    playback.seek(REPLAY_TICKS + 1);
+    assert_eq!(Snapshot::new(&playback), recorded);
+}
+
+#[test]
+fn same_seed_and_inputs_play_the_same_game() {
+    let play = || {
+        let mut simulation = Simulation::new(7);
+        simulation.start(GameMode::SinglePlayer);
+        let mut player = RandomPlayer::new(7);
+        simulation.step_n(1000, |simulation| vec![player.input(simulation)]);
+        (
+            simulation.score(),
+            simulation.lives(),
+            simulation.state(),
+            simulation
+                .balls()
+                .into_iter()
+                .map(|ball| (ball.position, ball.velocity))
+                .collect::<Vec<(Vec2, Vec2)>>(),
+        )
+    };
+    assert_eq!(play(), play());
+}